@@ -0,0 +1,90 @@
+//! Measures how long a keypair takes to decrypt a stream of chat messages.
+//!
+//! Run with `cargo bench --bench decrypt`.
+
+#[macro_use]
+extern crate criterion;
+extern crate crypto_module;
+
+use criterion::Criterion;
+use crypto_module::crypto_math::{number_to_string, string_to_number};
+use crypto_module::der::Reader;
+use crypto_module::rsa::{encrypt_oaep, Keypair, KeypairBuilder};
+
+/// builds the 2048-bit keypair and the OAEP ciphertexts of a short conversation.
+fn setup() -> (Keypair, Vec<String>) {
+    let keypair = KeypairBuilder::new()
+        .modulus_bits(2048)
+        .build(&[7; 32], &[11; 32])
+        .unwrap();
+    let public_key = keypair.public_key_display_wasm();
+
+    let messages = [
+        "hey, are you around?",
+        "yep, what's up",
+        "did you push the fix for the socket reconnect?",
+        "not yet, the tests are still running",
+        "ok ping me when it's in",
+        "will do",
+    ];
+
+    let ciphertexts = messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| encrypt_oaep(m, &public_key, &[i as u8 + 1; 32]).unwrap())
+        .collect();
+
+    (keypair, ciphertexts)
+}
+
+/// returns d, n, p, q, dp, dq and qinv of a keypair as decimal strings, the way `Keypair` used to store them.
+fn decimal_fields(keypair: &Keypair) -> Vec<String> {
+    // The RSAPrivateKey holds the version, n, e, d, p, q, dp, dq and qinv in that order.
+    let der = keypair.to_pkcs1_der();
+    let mut key = Reader::new(&der).read_sequence().unwrap();
+    let mut integers = (0..9).map(|_| key.read_integer().unwrap());
+    let (_version, n, _e, d) = (
+        integers.next().unwrap(),
+        integers.next().unwrap(),
+        integers.next().unwrap(),
+        integers.next().unwrap(),
+    );
+
+    vec![d, n]
+        .into_iter()
+        .chain(integers)
+        .map(|field| number_to_string(&field))
+        .collect()
+}
+
+fn decrypt_stream(c: &mut Criterion) {
+    let (keypair, ciphertexts) = setup();
+    let fields = decimal_fields(&keypair);
+
+    let mut group = c.benchmark_group("decrypt_stream");
+
+    group.bench_function("bigint_fields", |b| {
+        b.iter(|| {
+            for ciphertext in &ciphertexts {
+                keypair.decrypt_oaep(ciphertext).unwrap();
+            }
+        })
+    });
+
+    // The baseline parses the seven fields again before every message, as decryption did when they were Strings.
+    group.bench_function("decimal_string_fields", |b| {
+        b.iter(|| {
+            for ciphertext in &ciphertexts {
+                for field in &fields {
+                    criterion::black_box(string_to_number(field));
+                }
+                keypair.decrypt_oaep(ciphertext).unwrap();
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decrypt_stream);
+criterion_main!(benches);
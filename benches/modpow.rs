@@ -0,0 +1,89 @@
+//! Compares modular exponentiation with a `MontgomeryContext` against num-bigint's `BigInt::modpow`, with moduli
+//! and exponents of 512, 1024 and 2048 bits.
+//!
+//! Run with `cargo bench --bench modpow`.
+
+#[macro_use]
+extern crate criterion;
+extern crate crypto_module;
+extern crate num;
+extern crate rand;
+
+use criterion::{BenchmarkId, Criterion};
+use crypto_module::constant_time;
+use crypto_module::crypto_math::{bytes_to_number, ModRing, MontgomeryContext};
+use num::bigint::BigInt;
+use rand::{Rng, SeedableRng, StdRng};
+
+/// a random odd modulus with its top bit set, and a random base and exponent below it.
+fn setup(bits: usize) -> (BigInt, BigInt, BigInt) {
+    let mut rng: StdRng = SeedableRng::from_seed([bits as u8; 32]);
+    let mut random = |top: u8, bottom: u8| {
+        let mut bytes = vec![0u8; bits / 8];
+        rng.fill(&mut bytes[..]);
+        bytes[0] = bytes[0] & 0x7f | top;
+        bytes[bits / 8 - 1] |= bottom;
+        bytes_to_number(&bytes)
+    };
+
+    let modulus = random(0x80, 1);
+    (modulus, random(0, 0), random(0, 0))
+}
+
+fn modpow(c: &mut Criterion) {
+    let mut group = c.benchmark_group("modpow");
+    group.sample_size(20);
+
+    for &bits in &[512, 1024, 2048] {
+        let (modulus, base, exponent) = setup(bits);
+        let ring = MontgomeryContext::new(&modulus).unwrap();
+        assert_eq!(
+            ring.modpow(&base, &exponent),
+            base.modpow(&exponent, &modulus)
+        );
+
+        group.bench_with_input(BenchmarkId::new("bigint", bits), &bits, |b, _| {
+            b.iter(|| base.modpow(&exponent, &modulus))
+        });
+
+        group.bench_with_input(BenchmarkId::new("montgomery", bits), &bits, |b, _| {
+            b.iter(|| ring.modpow(&base, &exponent))
+        });
+
+        // Includes preparing the context, as for a one-off exponentiation.
+        group.bench_with_input(
+            BenchmarkId::new("montgomery_with_setup", bits),
+            &bits,
+            |b, _| {
+                b.iter(|| {
+                    MontgomeryContext::new(&modulus)
+                        .unwrap()
+                        .modpow(&base, &exponent)
+                })
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("constant_time", bits), &bits, |b, _| {
+            b.iter(|| constant_time::modpow(&base, &exponent, &modulus))
+        });
+    }
+
+    group.finish();
+}
+
+/// squaring modulo a 1024-bit number, as in the inner loop of Miller-Rabin: num-bigint needs a `modpow` by 2 for
+/// every step, a context a single Montgomery multiplication.
+fn square(c: &mut Criterion) {
+    let (modulus, base, _) = setup(1024);
+    let two = BigInt::from(2);
+    let ring = MontgomeryContext::new(&modulus).unwrap();
+    let residue = ring.to_montgomery(&base);
+
+    let mut group = c.benchmark_group("square_1024");
+    group.bench_function("bigint_modpow", |b| b.iter(|| base.modpow(&two, &modulus)));
+    group.bench_function("montgomery", |b| b.iter(|| ring.square(&residue)));
+    group.finish();
+}
+
+criterion_group!(benches, modpow, square);
+criterion_main!(benches);
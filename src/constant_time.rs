@@ -0,0 +1,170 @@
+use crypto_math::{limbs_needed, to_limbs, ModRing, MontgomeryContext, LIMB_BITS};
+use num::bigint::BigInt;
+use num_traits::Signed;
+use std::cmp;
+
+/// computes base^exponent mod modulus with a Montgomery ladder (see `MontgomeryContext`). Every step does the same
+/// multiplication and squaring whatever the exponent bit, and the bits are applied with masked swaps instead of
+/// branches, so the running time only depends on the sizes of the modulus and the exponent. Exponents shorter than
+/// the modulus are padded to its width, so that the length of a secret exponent is not leaked either. Returns None
+/// unless the modulus is odd and greater than one, or if the exponent is negative.
+///
+/// Only the exponentiation is constant time: converting the base and the result to and from `BigInt` is not, so
+/// the base should be public or blinded.
+///
+/// ## Reference
+/// See Joye and Yen, [The Montgomery Powering Ladder](https://doi.org/10.1007/3-540-36400-5_22).
+pub fn modpow(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    if exponent.is_negative() {
+        return None;
+    }
+    let ring = MontgomeryContext::new(modulus)?;
+
+    let bits = cmp::max(ring.width() * LIMB_BITS, exponent.bits());
+    let exponent = to_limbs(exponent, limbs_needed(bits));
+
+    // r1 = r0 * base holds after every step.
+    let mut r0 = ring.one();
+    let mut r1 = ring.to_montgomery(base);
+
+    for i in (0..bits).rev() {
+        let bit = (exponent[i / LIMB_BITS] >> (i % LIMB_BITS)) & 1;
+        swap(&mut r0.0, &mut r1.0, bit);
+        r1 = ring.mul(&r0, &r1);
+        r0 = ring.square(&r0);
+        swap(&mut r0.0, &mut r1.0, bit);
+    }
+
+    Some(ring.from_montgomery(&r0))
+}
+
+/// a - b over the width of a, and the borrow out (1 if b > a).
+pub(crate) fn subtract(a: &[u32], b: &[u32]) -> (Vec<u32>, u32) {
+    let mut borrow = 0;
+    let difference = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let (d, under_one) = a.overflowing_sub(b);
+            let (d, under_two) = d.overflowing_sub(borrow);
+            borrow = (under_one | under_two) as u32;
+            d
+        })
+        .collect();
+    (difference, borrow)
+}
+
+/// a + b over the width of a, and the carry out.
+pub(crate) fn add(a: &[u32], b: &[u32]) -> (Vec<u32>, u32) {
+    let mut carry = 0;
+    let sum = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let sum = u64::from(a) + u64::from(b) + u64::from(carry);
+            carry = (sum >> LIMB_BITS) as u32;
+            sum as u32
+        })
+        .collect();
+    (sum, carry)
+}
+
+/// doubles a in place and returns the bit shifted out.
+pub(crate) fn shift_left(a: &mut [u32]) -> u32 {
+    let mut carry = 0;
+    for limb in a.iter_mut() {
+        let out = *limb >> (LIMB_BITS - 1);
+        *limb = (*limb << 1) | carry;
+        carry = out;
+    }
+    carry
+}
+
+/// overwrites a with b if bit is 1, reading and writing every limb either way.
+pub(crate) fn select(a: &mut [u32], b: &[u32], bit: u32) {
+    let mask = bit.wrapping_neg();
+    for (a, &b) in a.iter_mut().zip(b) {
+        *a ^= mask & (*a ^ b);
+    }
+}
+
+/// swaps a and b if bit is 1, reading and writing every limb either way.
+pub(crate) fn swap(a: &mut [u32], b: &mut [u32], bit: u32) {
+    let mask = bit.wrapping_neg();
+    for (a, b) in a.iter_mut().zip(b.iter_mut()) {
+        let t = mask & (*a ^ *b);
+        *a ^= t;
+        *b ^= t;
+    }
+}
+
+#[cfg(test)]
+mod test_modpow {
+    use super::*;
+    use crypto_math::string_to_number;
+    use num_traits::One;
+    use rsa::test_keypair;
+
+    #[test]
+    fn matches_bigint_modpow() {
+        let k = test_keypair();
+        let cases = [(&k.n, &k.d), (&k.p, &k.dp), (&k.q, &k.dq), (&k.n, &k.e)];
+
+        for &(modulus, exponent) in &cases {
+            for base in &["0", "1", "2", "123456789123456789"] {
+                let base = string_to_number(base);
+                assert_eq!(
+                    modpow(&base, exponent, modulus),
+                    Some(base.modpow(exponent, modulus))
+                );
+            }
+
+            let base = modulus - BigInt::one();
+            assert_eq!(
+                modpow(&base, exponent, modulus),
+                Some(base.modpow(exponent, modulus))
+            );
+        }
+    }
+
+    #[test]
+    fn small_numbers() {
+        // Moduli of one and two limbs, with the base reduced or not.
+        for &modulus in &[3u64, 5, 3233, 65537, 4294967291, 4294967297] {
+            let modulus = BigInt::from(modulus);
+            for &base in &[0u64, 1, 2, 7, 1000, 4294967295] {
+                let base = BigInt::from(base);
+                for &exponent in &[0, 1, 2, 17, 65537] {
+                    let exponent = BigInt::from(exponent);
+                    assert_eq!(
+                        modpow(&base, &exponent, &modulus),
+                        Some(base.modpow(&exponent, &modulus))
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn long_exponent_and_negative_base() {
+        let modulus = string_to_number("3233");
+        let exponent = string_to_number("123456789123456789123456789");
+        assert_eq!(
+            modpow(&BigInt::from(42), &exponent, &modulus),
+            Some(BigInt::from(42).modpow(&exponent, &modulus))
+        );
+        assert_eq!(
+            modpow(&BigInt::from(-1), &BigInt::from(3), &modulus),
+            Some(modulus - 1)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_modulus() {
+        let two = BigInt::from(2);
+        assert_eq!(modpow(&two, &two, &BigInt::from(3234)), None);
+        assert_eq!(modpow(&two, &two, &BigInt::one()), None);
+        assert_eq!(modpow(&two, &two, &BigInt::from(-3)), None);
+        assert_eq!(modpow(&two, &BigInt::from(-1), &BigInt::from(3)), None);
+    }
+}
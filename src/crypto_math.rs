@@ -1,10 +1,17 @@
+use constant_time;
+use error::CryptoError;
 use num::{
-    bigint::{BigInt, RandBigInt, ToBigInt},
+    bigint::{BigInt, RandBigInt, Sign, ToBigInt},
     pow,
 };
+use num_traits::{Signed, ToPrimitive};
 use rand::{SeedableRng, StdRng};
+use std::cmp;
+use std::fmt;
+use wasm_bindgen::prelude::*;
 
-static SMALL_PRIMES: &'static [i32] = &[
+/// the primes below 1000, used for trial division.
+pub(crate) static SMALL_PRIMES: &[i32] = &[
     2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
     101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
     197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
@@ -16,7 +23,7 @@ static SMALL_PRIMES: &'static [i32] = &[
     937, 941, 947, 953, 967, 971, 977, 983, 991, 997,
 ];
 
-static BASES: &'static [i32] = &[2, 3, 5, 7, 11];
+static BASES: &[i32] = &[2, 3, 5, 7, 11];
 
 // Why lazy_static you may ask? Well, for one, try to compile this without lazy_static. You will
 // get an error saying statics can't be the result of an executed function. So, as per the crate
@@ -98,7 +105,8 @@ mod test_number_to_string {
         let num = BigInt::parse_bytes(
             b"123456789123456789123456789123456789123456789123456789",
             10,
-        ).unwrap();
+        )
+        .unwrap();
         let a = number_to_string(&num);
         let b = "123456789123456789123456789123456789123456789123456789".to_string();
         assert_eq!(a, b);
@@ -184,7 +192,8 @@ mod test_string_to_number_macro {
         let b = BigInt::parse_bytes(
             b"123456789123456789123456789123456789123456789123456789",
             10,
-        ).unwrap();
+        )
+        .unwrap();
         assert_eq!(a, b);
     }
 
@@ -203,7 +212,89 @@ mod test_string_to_number_macro {
     }
 }
 
-/// calculates and returns the greatest common denominator of two BigInt's.
+/// converts a non-negative BigInt to a big-endian byte string of exactly `len` bytes, padding with leading zeros.
+/// Returns None if the number is negative or does not fit.
+///
+/// ## Reference
+/// This is I2OSP from [RFC 8017, section 4.1](https://tools.ietf.org/html/rfc8017#section-4.1).
+pub fn number_to_bytes(num: &BigInt, len: usize) -> Option<Vec<u8>> {
+    if num.sign() == Sign::Minus {
+        return None;
+    }
+
+    let (_, bytes) = num.to_bytes_be();
+    let bytes: &[u8] = if bytes == [0] { &[] } else { &bytes };
+
+    if bytes.len() > len {
+        return None;
+    }
+
+    let mut padded = vec![0; len - bytes.len()];
+    padded.extend_from_slice(bytes);
+    Some(padded)
+}
+
+#[cfg(test)]
+mod test_number_to_bytes {
+    use super::*;
+
+    #[test]
+    fn zero() {
+        assert_eq!(number_to_bytes(&ZERO, 0), Some(vec![]));
+        assert_eq!(number_to_bytes(&ZERO, 2), Some(vec![0, 0]));
+    }
+
+    #[test]
+    fn padded() {
+        let num = string_to_number("258");
+        assert_eq!(number_to_bytes(&num, 4), Some(vec![0, 0, 1, 2]));
+    }
+
+    #[test]
+    fn too_large() {
+        let num = string_to_number("65536");
+        assert_eq!(number_to_bytes(&num, 2), None);
+    }
+
+    #[test]
+    fn negative() {
+        let num = string_to_number("-5");
+        assert_eq!(number_to_bytes(&num, 2), None);
+    }
+}
+
+/// converts a big-endian byte string to a non-negative BigInt.
+///
+/// ## Reference
+/// This is OS2IP from [RFC 8017, section 4.2](https://tools.ietf.org/html/rfc8017#section-4.2).
+pub fn bytes_to_number(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+#[cfg(test)]
+mod test_bytes_to_number {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(bytes_to_number(&[]), *ZERO);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(bytes_to_number(&[0, 0, 1, 2]), string_to_number("258"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let num = string_to_number("123456789123456789123456789");
+        let bytes = number_to_bytes(&num, 16).unwrap();
+        assert_eq!(bytes_to_number(&bytes), num);
+    }
+}
+
+/// calculates and returns the greatest common denominator of two BigInt's. Every step divides by the previous
+/// remainder, so the modulus changes each time and a fixed-modulus context like `BarrettContext` does not apply.
 ///
 /// ## Reference
 /// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
@@ -442,7 +533,8 @@ mod test_extended_gcd {
     }
 }
 
-/// returns the modular inverse of a BigInt modulo n (another BigInt).
+/// returns the modular inverse of a BigInt modulo n (another BigInt). The work is in Euclid's algorithm, see `gcd`,
+/// and the one reduction modulo n at the end is not worth preparing a `BarrettContext` for.
 ///
 /// ## Reference
 /// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
@@ -516,253 +608,1917 @@ mod test_mod_inverse {
     }
 }
 
-/// probabilistically deduces whether a given number is prime.
-///
-/// ## Reference
-/// Check out: [https://rosettacode.org/wiki/Miller%E2%80%93Rabin_primality_test](https://rosettacode.org/wiki/Miller%E2%80%93Rabin_primality_test)
-pub fn miller_rabin(n: &BigInt, seed: &[u8]) -> bool {
-    let n_minus_one = n - &*ONE;
+/// bits in a limb, the digit of the numbers a `MontgomeryContext` works with.
+pub(crate) const LIMB_BITS: usize = 32;
 
-    if n == &*TWO {
-        return true;
-    }
+/// the number of limbs needed for a number of bits.
+pub(crate) fn limbs_needed(bits: usize) -> usize {
+    cmp::max(1, bits.div_ceil(LIMB_BITS))
+}
 
-    if *n < *TWO || n % &*TWO == *ZERO {
-        return false;
+/// converts a number to width limbs, least significant first. The sign is ignored, and the number must be below
+/// 2^(32 · width).
+pub(crate) fn to_limbs(num: &BigInt, width: usize) -> Vec<u32> {
+    let mut limbs = vec![0; width];
+    for (i, &byte) in num.to_bytes_le().1.iter().enumerate() {
+        limbs[i / 4] |= u32::from(byte) << (8 * (i % 4));
     }
+    limbs
+}
 
-    let mut s: BigInt = ZERO.clone();
-    let mut d: BigInt = n - &*ONE;
-
-    while &d % &*TWO == *ZERO {
-        s += &*ONE;
-        d /= &*TWO;
-    }
+/// converts limbs, least significant first, back to a number.
+pub(crate) fn from_limbs(limbs: &[u32]) -> BigInt {
+    let bytes: Vec<u8> = limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+    BigInt::from_bytes_le(Sign::Plus, &bytes)
+}
 
-    let mut rng: StdRng = SeedableRng::from_seed(from_slice(&seed));
+/// arithmetic modulo a fixed number n. Implemented by `MontgomeryContext` and `BarrettContext`, which prepare n once
+/// so that every reduction after that is cheaper than a division; code that is generic over `ModRing` works with
+/// either. Elements are kept in whatever form the implementation works in, so elements of different rings must not
+/// be mixed.
+pub trait ModRing {
+    /// a number between 0 and n - 1, in the form this ring works in.
+    type Element: Clone + PartialEq + fmt::Debug;
 
-    // 50 here is a parameter for accuracy
-    for _ in 0..50 {
-        let a_num = rng.gen_bigint_range(&*TWO, &n_minus_one);
+    /// the modulus n.
+    fn modulus(&self) -> &BigInt;
 
-        let gcd_num = gcd(&a_num, n);
+    /// the element for a, reducing it modulo n first.
+    fn to_element(&self, a: &BigInt) -> Self::Element;
 
-        if gcd_num != *ONE {
-            return false;
-        }
+    /// the number between 0 and n - 1 that a stands for.
+    fn to_number(&self, a: &Self::Element) -> BigInt;
 
-        let mut x_num = a_num.modpow(&d, n);
+    /// a + b.
+    fn add(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
 
-        if x_num == *ONE || x_num == n_minus_one {
-            continue;
-        }
+    /// a - b.
+    fn sub(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
 
-        let mut is_witness = true;
-        let mut r = ONE.clone();
+    /// a · b.
+    fn mul(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
 
-        while r < s && is_witness {
-            x_num = x_num.modpow(&*TWO, n);
+    /// a · a.
+    fn square(&self, a: &Self::Element) -> Self::Element {
+        self.mul(a, a)
+    }
 
-            if x_num == n_minus_one {
-                is_witness = false;
-            }
+    /// 1.
+    fn one(&self) -> Self::Element {
+        self.to_element(&ONE)
+    }
 
-            r += &*ONE;
+    /// base^exponent, four exponent bits at a time. The sign of the exponent is ignored. The time taken
+    /// depends on the exponent, so use `constant_time::modpow` for secret exponents.
+    fn pow(&self, base: &Self::Element, exponent: &BigInt) -> Self::Element {
+        // base^0, base^1, ..., base^15
+        let mut table = vec![self.one()];
+        for i in 1..16 {
+            let next = self.mul(&table[i - 1], base);
+            table.push(next);
         }
 
-        if is_witness {
-            return false;
+        let mut result: Option<Self::Element> = None;
+        for &byte in exponent.to_bytes_be().1.iter() {
+            for &nibble in &[byte >> 4, byte & 0xf] {
+                result = match result {
+                    // Skip the squarings of 1 before the first set bit.
+                    None if nibble == 0 => None,
+                    None => Some(table[nibble as usize].clone()),
+                    Some(mut r) => {
+                        for _ in 0..4 {
+                            r = self.square(&r);
+                        }
+                        if nibble != 0 {
+                            r = self.mul(&r, &table[nibble as usize]);
+                        }
+                        Some(r)
+                    }
+                };
+            }
         }
+
+        result.unwrap_or_else(|| self.one())
     }
 
-    true
+    /// the inverse of a, if a and n are coprime. Like `mod_inverse`, this is not constant time.
+    fn inverse(&self, a: &Self::Element) -> Option<Self::Element> {
+        mod_inverse(&self.to_number(a), self.modulus()).map(|inverse| self.to_element(&inverse))
+    }
+
+    /// base^exponent mod n for plain numbers, like `BigInt::modpow`.
+    fn modpow(&self, base: &BigInt, exponent: &BigInt) -> BigInt {
+        self.to_number(&self.pow(&self.to_element(base), exponent))
+    }
 }
 
-/// determines whether a given number is prime by first running some simple primality tests through a small set of
-/// known primes and bases, and then, if all these basic tests pass, returns the result of the Miller-Rabin test.
+/// a number a in Montgomery form, a · R mod n, for the modulus of the `MontgomeryContext` that made it. Numbers from
+/// different contexts must not be mixed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Residue(pub(crate) Vec<u32>);
+
+/// an odd modulus n prepared for Montgomery multiplication, which replaces the division by n in every modular
+/// multiplication with a division by R = 2^(32 · width), i.e. a shift. Preparing costs about as much as a few
+/// multiplications, so a context pays off when many operations share a modulus, as in primality tests and RSA.
+///
+/// Addition, subtraction, multiplication and squaring take the same time for all inputs of a context, which
+/// `constant_time::modpow` relies on; `pow` and `inverse` do not.
 ///
 /// ## Reference
-/// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
-pub fn is_prime(n: &BigInt, seed: &[u8]) -> bool {
-    let n_minus_one = n - &*ONE;
+/// See Montgomery, [Modular Multiplication Without Trial Division](https://doi.org/10.1090/S0025-5718-1985-0777282-X),
+/// and section 14.3.2 of the [Handbook of Applied Cryptography](https://cacr.uwaterloo.ca/hac/).
+#[derive(Debug, Clone)]
+pub struct MontgomeryContext {
+    /// the modulus
+    modulus: BigInt,
+    /// the modulus as limbs
+    n: Vec<u32>,
+    /// -n^-1 mod 2^32
+    n_prime: u32,
+    /// R^2 mod n, to bring numbers into Montgomery form
+    r_squared: Vec<u32>,
+}
 
-    if *n < *TWO {
-        return false;
-    }
+impl MontgomeryContext {
+    /// prepares a modulus. Returns None unless it is odd and greater than one.
+    pub fn new(modulus: &BigInt) -> Option<MontgomeryContext> {
+        if *modulus <= *ONE || modulus % &*TWO == *ZERO {
+            return None;
+        }
 
-    let small_primes_as_bigints: Vec<BigInt> = SMALL_PRIMES
-        .iter()
-        .map(|x| x.to_bigint().unwrap())
-        .collect();
-    let is_small_prime = small_primes_as_bigints.contains(n);
+        let width = limbs_needed(modulus.bits());
+        let n = to_limbs(modulus, width);
 
-    if is_small_prime {
-        return true;
-    }
+        // Newton's iteration doubles the number of correct low bits of n^-1 each time: 1, 2, 4, ..., 32.
+        let mut inverse: u32 = 1;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u32.wrapping_sub(n[0].wrapping_mul(inverse)));
+        }
 
-    for prime in small_primes_as_bigints {
-        if n % &prime == *ZERO {
-            return false;
+        // R^2 mod n by doubling 1 as many times as R^2 has bits, reducing after each doubling. Unlike a division,
+        // this takes the same time for every modulus of a size, which matters when the modulus is a secret prime.
+        let mut r_squared = to_limbs(&ONE, width);
+        for _ in 0..2 * width * LIMB_BITS {
+            let carry = constant_time::shift_left(&mut r_squared);
+            let (difference, borrow) = constant_time::subtract(&r_squared, &n);
+            constant_time::select(&mut r_squared, &difference, carry | (borrow ^ 1));
         }
+
+        Some(MontgomeryContext {
+            modulus: modulus.clone(),
+            n,
+            n_prime: inverse.wrapping_neg(),
+            r_squared,
+        })
     }
 
-    let bases_as_bigints: Vec<BigInt> = BASES.iter().map(|x| x.to_bigint().unwrap()).collect();
+    /// the number of limbs in every `Residue` of this context.
+    pub fn width(&self) -> usize {
+        self.n.len()
+    }
 
-    for base in &bases_as_bigints {
-        if base.modpow(&n_minus_one, n) != *ONE {
-            return false;
+    /// brings a into Montgomery form, reducing it modulo n first.
+    pub fn to_montgomery(&self, a: &BigInt) -> Residue {
+        let mut a = a % &self.modulus;
+        if a < *ZERO {
+            a += &self.modulus;
         }
+        self.mul(
+            &Residue(to_limbs(&a, self.width())),
+            &Residue(self.r_squared.clone()),
+        )
     }
 
-    miller_rabin(n, seed)
+    /// the number that a stands for, between 0 and n - 1.
+    pub fn from_montgomery(&self, a: &Residue) -> BigInt {
+        from_limbs(&self.mul(a, &Residue(to_limbs(&ONE, self.width()))).0)
+    }
 }
 
-#[cfg(test)]
-mod test_is_prime_and_rabin_miller {
-    use super::*;
+impl ModRing for MontgomeryContext {
+    type Element = Residue;
 
-    #[test]
-    fn miniscule_prime() {
-        let a = &string_to_number("3");
-        let expected = true;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn modulus(&self) -> &BigInt {
+        &self.modulus
     }
 
-    #[test]
-    fn miniscule_not_prime() {
-        let a = &string_to_number("4");
-        let expected = false;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn to_element(&self, a: &BigInt) -> Residue {
+        self.to_montgomery(a)
     }
 
-    #[test]
-    fn tiny_prime() {
-        let a = &string_to_number("1049");
-        let expected = true;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn to_number(&self, a: &Residue) -> BigInt {
+        self.from_montgomery(a)
     }
 
-    #[test]
-    fn tiny_not_prime() {
-        let a = &string_to_number("1050");
-        let expected = false;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    /// a + b, subtracting n if the sum reaches it. The subtraction is always computed and conditionally kept.
+    fn add(&self, a: &Residue, b: &Residue) -> Residue {
+        let (mut sum, carry) = constant_time::add(&a.0, &b.0);
+        let (difference, borrow) = constant_time::subtract(&sum, &self.n);
+        constant_time::select(&mut sum, &difference, carry | (borrow ^ 1));
+        Residue(sum)
     }
 
-    #[test]
-    fn small_prime() {
-        let a = &string_to_number("100103");
-        let expected = true;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    /// a - b, adding n back if the difference is negative. The addition is always computed and conditionally kept.
+    fn sub(&self, a: &Residue, b: &Residue) -> Residue {
+        let (mut difference, borrow) = constant_time::subtract(&a.0, &b.0);
+        let (sum, _) = constant_time::add(&difference, &self.n);
+        constant_time::select(&mut difference, &sum, borrow);
+        Residue(difference)
     }
 
-    #[test]
-    fn small_not_prime() {
-        let a = &string_to_number("100105");
-        let expected = false;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    /// the product of a and b, by finely integrated operand scanning (FIOS): each limb of b is multiplied in and
+    /// reduced in the same pass. The final subtraction of n is always computed and conditionally kept, so the time
+    /// taken does not depend on a or b.
+    fn mul(&self, a: &Residue, b: &Residue) -> Residue {
+        let width = self.width();
+        let (a, n) = (&a.0[..width], &self.n[..width]);
+        let mut t = vec![0u32; width + 1];
+
+        for &b_i in &b.0 {
+            // t = (t + a * b_i + m * n) / 2^32, where m makes the lowest limb of the sum zero. Both products are
+            // added in one pass, each with its own carry.
+            let m = t[0]
+                .wrapping_add(a[0].wrapping_mul(b_i))
+                .wrapping_mul(self.n_prime);
+
+            let sum = u64::from(t[0]) + u64::from(a[0]) * u64::from(b_i);
+            let mut carry_ab = sum >> LIMB_BITS;
+            let mut carry_mn =
+                (u64::from(sum as u32) + u64::from(m) * u64::from(n[0])) >> LIMB_BITS;
+
+            for j in 1..width {
+                let sum = u64::from(t[j]) + u64::from(a[j]) * u64::from(b_i) + carry_ab;
+                carry_ab = sum >> LIMB_BITS;
+                let sum = u64::from(sum as u32) + u64::from(m) * u64::from(n[j]) + carry_mn;
+                carry_mn = sum >> LIMB_BITS;
+                t[j - 1] = sum as u32;
+            }
+
+            let sum = u64::from(t[width]) + carry_ab + carry_mn;
+            t[width - 1] = sum as u32;
+            t[width] = (sum >> LIMB_BITS) as u32;
+        }
+
+        // t < 2n, so one subtraction is enough.
+        let overflow = t[width];
+        t.truncate(width);
+        let (difference, borrow) = constant_time::subtract(&t, n);
+        constant_time::select(&mut t, &difference, overflow | (borrow ^ 1));
+        Residue(t)
     }
+}
+
+#[cfg(test)]
+mod test_montgomery_context {
+    use super::*;
 
     #[test]
-    fn medium_prime() {
-        let a = &string_to_number("100000015333");
-        let expected = true;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn rejects_even_and_small_moduli() {
+        for modulus in &["0", "1", "2", "3234", "-3"] {
+            assert!(MontgomeryContext::new(&string_to_number(modulus)).is_none());
+        }
     }
 
     #[test]
-    fn medium_not_prime() {
-        let a = &string_to_number("100000015334");
-        let expected = false;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn round_trip() {
+        let ring = MontgomeryContext::new(&string_to_number("3233")).unwrap();
+        for a in &["0", "1", "2", "3232"] {
+            let a = string_to_number(a);
+            assert_eq!(ring.from_montgomery(&ring.to_montgomery(&a)), a);
+        }
+        assert_eq!(
+            ring.from_montgomery(&ring.to_montgomery(&string_to_number("-1"))),
+            string_to_number("3232")
+        );
+        assert_eq!(
+            ring.from_montgomery(&ring.to_montgomery(&string_to_number("3234"))),
+            *ONE
+        );
     }
 
     #[test]
-    fn large_prime() {
-        let a = &string_to_number("335184372088831");
-        let expected = true;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn multiply_and_square() {
+        let modulus = string_to_number(
+            "119570724854925789102189739820619173336989011557175308375903167094730580033819096662530796118522076478241773710770830088610110126361256404655578216144473303486453947720998046445814563132975512591924879866157323501017042987678283134684517190369815229157482329808926904089471689116254014429012042009805032570091",
+        );
+        let ring = MontgomeryContext::new(&modulus).unwrap();
+        let a = string_to_number("123456789123456789123456789");
+        let b = &modulus - &*TWO;
+
+        let product = ring.mul(&ring.to_montgomery(&a), &ring.to_montgomery(&b));
+        assert_eq!(ring.from_montgomery(&product), &a * &b % &modulus);
+        let square = ring.square(&ring.to_montgomery(&b));
+        assert_eq!(ring.from_montgomery(&square), &b * &b % &modulus);
     }
 
     #[test]
-    fn large_not_prime() {
-        let a = &string_to_number("335184372088832");
-        let expected = false;
-        assert_eq!(is_prime(a, test_seed()), expected);
+    fn matches_bigint_modpow() {
+        for modulus in &["3", "3233", "4294967291", "4294967297", "100000015333"] {
+            let modulus = string_to_number(modulus);
+            let ring = MontgomeryContext::new(&modulus).unwrap();
+
+            for base in &["0", "1", "2", "7", "4294967295"] {
+                for exponent in &[
+                    "0",
+                    "1",
+                    "2",
+                    "15",
+                    "16",
+                    "17",
+                    "65537",
+                    "123456789123456789",
+                ] {
+                    let (base, exponent) = (string_to_number(base), string_to_number(exponent));
+                    assert_eq!(
+                        ring.modpow(&base, &exponent),
+                        base.modpow(&exponent, &modulus)
+                    );
+                }
+            }
+        }
     }
 }
 
-/// generates a random prime for a given seed.
+/// a modulus n prepared for Barrett reduction, which replaces the division in x mod n with two multiplications by
+/// a precomputed approximation of 1/n. Unlike a `MontgomeryContext` it works for any modulus above one and keeps
+/// numbers in their normal form, so a context is worth it even for a handful of reductions of numbers below n^2.
 ///
 /// ## Reference
-/// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
-pub fn generate_prime(bits: usize, tries: usize, seed: &[u8]) -> Option<BigInt> {
-    let bits_minus_one = bits - 1;
-    let x = pow(TWO.clone(), bits_minus_one);
-    let y = &*TWO * &x;
+/// See algorithm 14.42 of the [Handbook of Applied Cryptography](https://cacr.uwaterloo.ca/hac/), with base 2.
+#[derive(Debug, Clone)]
+pub struct BarrettContext {
+    /// the modulus
+    modulus: BigInt,
+    /// the number of bits in the modulus
+    k: usize,
+    /// floor(4^k / n)
+    mu: BigInt,
+}
 
-    let mut rng: StdRng = SeedableRng::from_seed(from_slice(&seed));
+impl BarrettContext {
+    /// prepares a modulus. Returns None unless it is greater than one.
+    pub fn new(modulus: &BigInt) -> Option<BarrettContext> {
+        if *modulus <= *ONE {
+            return None;
+        }
 
-    for _ in 0..tries {
-        let mut n = rng.gen_bigint_range(&x, &y);
+        let k = modulus.bits();
+        Some(BarrettContext {
+            modulus: modulus.clone(),
+            k,
+            mu: (&*ONE << (2 * k)) / modulus,
+        })
+    }
+
+    /// x mod n, between 0 and n - 1. Numbers from 0 up to 4^k, which includes every product of two reduced
+    /// numbers, are reduced without a division; others fall back to `%`.
+    pub fn reduce(&self, x: &BigInt) -> BigInt {
+        if *x < *ZERO || x.bits() > 2 * self.k {
+            let mut r = x % &self.modulus;
+            if r < *ZERO {
+                r += &self.modulus;
+            }
+            return r;
+        }
 
-        if &n % &*TWO == *ZERO {
-            n += 1;
+        // q underestimates x / n by at most 2.
+        let q = ((x >> (self.k - 1)) * &self.mu) >> (self.k + 1);
+        let mut r = x - q * &self.modulus;
+        while r >= self.modulus {
+            r -= &self.modulus;
         }
+        r
+    }
+}
 
-        let q = is_prime(&n, seed);
+impl ModRing for BarrettContext {
+    type Element = BigInt;
 
-        if q {
-            return Some(n);
+    fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+
+    fn to_element(&self, a: &BigInt) -> BigInt {
+        self.reduce(a)
+    }
+
+    fn to_number(&self, a: &BigInt) -> BigInt {
+        a.clone()
+    }
+
+    fn add(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let sum = a + b;
+        if sum >= self.modulus {
+            sum - &self.modulus
+        } else {
+            sum
         }
     }
 
-    None
+    fn sub(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let difference = a - b;
+        if difference < *ZERO {
+            difference + &self.modulus
+        } else {
+            difference
+        }
+    }
+
+    fn mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        self.reduce(&(a * b))
+    }
 }
 
 #[cfg(test)]
-mod test_generate_prime {
+mod test_barrett_context {
     use super::*;
 
     #[test]
-    fn miniscule_prime() {
-        let prime = generate_prime(2, 1000, test_seed());
-        assert_eq!(prime, Some(string_to_number("3")));
+    fn rejects_small_moduli() {
+        for modulus in &["0", "1", "-3"] {
+            assert!(BarrettContext::new(&string_to_number(modulus)).is_none());
+        }
+        assert!(BarrettContext::new(&TWO).is_some());
     }
 
     #[test]
-    fn tiny_prime() {
-        let prime = generate_prime(8, 1000, test_seed());
-        assert_eq!(prime, Some(string_to_number("193")));
+    fn matches_remainder() {
+        for modulus in &["2", "3", "1000", "3233", "4294967296", "100000015333"] {
+            let modulus = string_to_number(modulus);
+            let ring = BarrettContext::new(&modulus).unwrap();
+            let square = &modulus * &modulus;
+
+            for x in &[
+                ZERO.clone(),
+                ONE.clone(),
+                &modulus - &*ONE,
+                modulus.clone(),
+                &modulus + &*ONE,
+                &square - &*ONE,
+                square.clone(),
+                &square * &square + &*ONE,
+                string_to_number("123456789123456789123456789"),
+            ] {
+                assert_eq!(ring.reduce(x), x % &modulus);
+            }
+            assert_eq!(ring.reduce(&string_to_number("-1")), &modulus - &*ONE);
+        }
     }
 
     #[test]
-    fn medium_prime() {
-        let prime = generate_prime(64, 1000, test_seed());
-        assert_eq!(prime, Some(string_to_number("10057321802802702503")));
+    fn matches_bigint_modpow() {
+        // Even moduli too, which Montgomery multiplication cannot handle.
+        for modulus in &["2", "1000", "3233", "4294967296", "100000015333"] {
+            let modulus = string_to_number(modulus);
+            let ring = BarrettContext::new(&modulus).unwrap();
+
+            for base in &["0", "1", "2", "7", "4294967295"] {
+                for exponent in &["0", "1", "2", "17", "65537", "123456789123456789"] {
+                    let (base, exponent) = (string_to_number(base), string_to_number(exponent));
+                    assert_eq!(
+                        ring.modpow(&base, &exponent),
+                        base.modpow(&exponent, &modulus)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_mod_ring {
+    use super::*;
+
+    /// checks every operation of ring against plain BigInt arithmetic, for a few numbers below the modulus.
+    fn check<R: ModRing>(ring: &R) {
+        let n = ring.modulus().clone();
+        let numbers = [
+            ZERO.clone(),
+            ONE.clone(),
+            TWO.clone(),
+            &n / &*TWO,
+            &n - &*TWO,
+            &n - &*ONE,
+        ];
+        let reduce = |x: BigInt| ((x % &n) + &n) % &n;
+
+        for a in &numbers {
+            let a_element = ring.to_element(a);
+            assert_eq!(ring.to_number(&a_element), *a);
+            assert_eq!(ring.to_number(&ring.square(&a_element)), reduce(a * a));
+
+            match ring.inverse(&a_element) {
+                Some(inverse) => assert_eq!(ring.mul(&a_element, &inverse), ring.one()),
+                None => assert_ne!(gcd(a, &n), *ONE),
+            }
+
+            for b in &numbers {
+                let b_element = ring.to_element(b);
+                let sum = ring.add(&a_element, &b_element);
+                let difference = ring.sub(&a_element, &b_element);
+                let product = ring.mul(&a_element, &b_element);
+
+                assert_eq!(ring.to_number(&sum), reduce(a + b));
+                assert_eq!(ring.to_number(&difference), reduce(a - b));
+                assert_eq!(ring.to_number(&product), reduce(a * b));
+            }
+        }
     }
 
     #[test]
-    fn large_prime() {
-        let prime = generate_prime(256, 1000, test_seed());
-        assert_eq!(
-            prime,
-            Some(string_to_number(
-                "91585194753718779240055081770127290880143452499556598946529982336565467053363"
-            ))
-        );
+    fn both_contexts_agree_with_bigint() {
+        let moduli = [
+            "3",
+            "3233",
+            "4294967291",
+            "4294967297",
+            "18446744073709551629",
+            "119570724854925789102189739820619173336989011557175308375903167094730580033819096662530796118522076478241773710770830088610110126361256404655578216144473303486453947720998046445814563132975512591924879866157323501017042987678283134684517190369815229157482329808926904089471689116254014429012042009805032570091",
+        ];
+        for modulus in &moduli {
+            let modulus = string_to_number(modulus);
+            check(&MontgomeryContext::new(&modulus).unwrap());
+            check(&BarrettContext::new(&modulus).unwrap());
+        }
+    }
+}
+
+/// writes a positive number m as 2^s · d with d odd, and returns d and s.
+fn odd_part(m: &BigInt) -> (BigInt, usize) {
+    let mut d = m.clone();
+    let mut s = 0;
+
+    while &d % &*TWO == *ZERO {
+        s += 1;
+        d /= &*TWO;
+    }
+
+    (d, s)
+}
+
+/// whether a is a witness to the compositeness of n = 2^s · d + 1, with d odd, computing in a ring modulo n.
+/// Primes have no witnesses, and at least three quarters of the numbers below an odd composite n are witnesses.
+fn is_witness<R: ModRing>(ring: &R, a: &BigInt, d: &BigInt, s: usize) -> bool {
+    let one = ring.one();
+    let minus_one = ring.sub(&ring.to_element(&ZERO), &one);
+
+    let mut x = ring.pow(&ring.to_element(a), d);
+    if x == one || x == minus_one {
+        return false;
+    }
+
+    for _ in 1..s {
+        x = ring.square(&x);
+        if x == minus_one {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// whether base^(n - 1) = 1 modulo n, computing in a ring modulo n. This holds for every base coprime to a
+/// prime n, by Fermat's little theorem.
+fn is_fermat_probable_prime<R: ModRing>(ring: &R, base: &BigInt) -> bool {
+    ring.pow(&ring.to_element(base), &(ring.modulus() - &*ONE)) == ring.one()
+}
+
+/// bounds below which the first few small primes are proven to be enough Miller-Rabin bases: every odd composite
+/// n below the bound has a witness among them. Each bound is the smallest strong pseudoprime to all of its bases.
+///
+/// ## Reference
+/// See Jaeschke, [On Strong Pseudoprimes to Several Bases](https://doi.org/10.1090/S0025-5718-1993-1192971-8), and
+/// Sorenson and Webster, [Strong Pseudoprimes to Twelve Prime Bases](https://doi.org/10.1090/mcom/3134).
+static DETERMINISTIC_BOUNDS: &[(&str, usize)] = &[
+    ("2047", 1),
+    ("1373653", 2),
+    ("25326001", 3),
+    ("3215031751", 4),
+    ("2152302898747", 5),
+    ("3474749660383", 6),
+    ("341550071728321", 7),
+    ("3825123056546413051", 9),
+    ("318665857834031151167461", 12),
+    ("3317044064679887385961981", 13),
+];
+
+lazy_static! {
+    /// `DETERMINISTIC_BOUNDS` as BigInts.
+    static ref DETERMINISTIC_BOUND_NUMBERS: Vec<(BigInt, usize)> = DETERMINISTIC_BOUNDS
+        .iter()
+        .map(|&(bound, bases)| (string_to_number(bound), bases))
+        .collect();
+}
+
+/// the smallest set of Miller-Rabin bases proven to be enough for n, or None if n is too large for any known set.
+pub fn deterministic_bases(n: &BigInt) -> Option<&'static [i32]> {
+    DETERMINISTIC_BOUND_NUMBERS
+        .iter()
+        .find(|&(bound, _)| n < bound)
+        .map(|&(_, bases)| &SMALL_PRIMES[..bases])
+}
+
+/// deterministically decides whether a given number is prime with Miller-Rabin, using the bases from
+/// `deterministic_bases`. Returns None if n is too large for a proven set of bases, about 3.3 · 10^24.
+pub fn deterministic_miller_rabin(n: &BigInt) -> Option<bool> {
+    let bases = deterministic_bases(n)?;
+
+    if n == &*TWO {
+        return Some(true);
     }
+
+    // Numbers below 2 and even numbers other than 2 are not prime, and have no context.
+    let ring = match MontgomeryContext::new(n) {
+        Some(ring) => ring,
+        None => return Some(false),
+    };
+    let (d, s) = odd_part(&(n - &*ONE));
+
+    // A base that is a multiple of n proves nothing, which only happens when n is one of the bases.
+    let is_composite = bases
+        .iter()
+        .map(|&base| BigInt::from(base))
+        .filter(|base| base % n != *ZERO)
+        .any(|base| is_witness(&ring, &base, &d, s));
+    Some(!is_composite)
+}
+
+/// Miller-Rabin rounds for numbers that may have been chosen by an adversary, such as the primes of an imported key.
+/// At most a quarter of the bases are not witnesses for any odd composite, so 50 rounds err with probability at most
+/// 4^-50 = 2^-100 whatever the number.
+pub const WORST_CASE_ROUNDS: usize = 50;
+
+/// the error probability, as a power of two, that primes are generated with unless told otherwise.
+pub const DEFAULT_SECURITY_BITS: u32 = 128;
+
+/// probabilistically deduces whether a given number is prime with `WORST_CASE_ROUNDS` rounds of Miller-Rabin. Fails
+/// if the seed is shorter than 32 bytes.
+pub fn miller_rabin(n: &BigInt, seed: &[u8]) -> Result<bool, CryptoError> {
+    miller_rabin_with_rounds(n, WORST_CASE_ROUNDS, seed)
 }
 
-/// converts a vector of bytes to an array of u8's.
+/// probabilistically deduces whether a given number is prime with the given number of Miller-Rabin rounds, each with
+/// a base drawn from the seed. Fails if the seed is shorter than 32 bytes.
+///
+/// Numbers small enough for `deterministic_miller_rabin` are tested with a proven set of bases instead, which gives
+/// the same, certain, answer for every seed.
 ///
 /// ## Reference
-/// Ref: https://stackoverflow.com/questions/29570607/is-there-a-good-way-to-convert-a-vect-to-an-array
-fn from_slice(bytes: &[u8]) -> [u8; 32] {
-    let mut array = [0; 32];
-    let bytes = &bytes[..array.len()]; // panics if not enough data
-    array.copy_from_slice(bytes);
-    array
+/// Check out: [https://rosettacode.org/wiki/Miller%E2%80%93Rabin_primality_test](https://rosettacode.org/wiki/Miller%E2%80%93Rabin_primality_test)
+pub fn miller_rabin_with_rounds(
+    n: &BigInt,
+    rounds: usize,
+    seed: &[u8],
+) -> Result<bool, CryptoError> {
+    let n_minus_one = n - &*ONE;
+
+    let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+    if let Some(answer) = deterministic_miller_rabin(n) {
+        return Ok(answer);
+    }
+
+    // Numbers below 2 and even numbers other than 2 are not prime, and have no context.
+    let ring = match MontgomeryContext::new(n) {
+        Some(ring) => ring,
+        None => return Ok(false),
+    };
+
+    let (d, s) = odd_part(&n_minus_one);
+
+    for _ in 0..rounds {
+        let a_num = rng.gen_bigint_range(&TWO, &n_minus_one);
+
+        let gcd_num = gcd(&a_num, n);
+
+        if gcd_num != *ONE {
+            return Ok(false);
+        }
+
+        if is_witness(&ring, &a_num, &d, s) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// the fewest Miller-Rabin rounds after which a random odd number of the given bit length that passes all of them
+/// is composite with probability at most 2^-security_bits. Random candidates need far fewer rounds than the worst
+/// case, and the larger they are the fewer: for 2^-128, 12 rounds at 512 bits and 4 at 1536 bits. Never more than
+/// the rounds that bound the error for every number, half of security_bits, and never fewer than one.
+///
+/// Only use this for numbers drawn uniformly at random; see `search_margin` for the candidates of a prime search,
+/// and `WORST_CASE_ROUNDS` for numbers from elsewhere.
+///
+/// ## Reference
+/// The rounds come from the bound of Damgård, Landrock and Pomerance, [Average Case Error Estimates for the Strong
+/// Probable Prime Test](https://doi.org/10.1090/S0025-5718-1993-1189518-9), computed as in appendix F.1 of
+/// [FIPS 186-4](https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.186-4.pdf), which is how the tables of its appendix
+/// C.3 were made.
+pub fn miller_rabin_rounds(bits: usize, security_bits: u32) -> usize {
+    let worst_case = cmp::max(1, (security_bits as usize).div_ceil(2));
+    (1..worst_case)
+        .find(|&rounds| log2_error_bound(bits, rounds) <= -f64::from(security_bits))
+        .unwrap_or(worst_case)
+}
+
+/// log2 of the bound p_(k,t) from appendix F.1 of FIPS 186-4 on the probability that a random odd k-bit number that
+/// passes t rounds of Miller-Rabin is composite, minimised over its parameter M, with 3 <= M <= 2 sqrt(k - 1).
+/// Infinite for k below 4, where M has no values. Computed with logarithms, as the terms are far too small for an
+/// f64.
+fn log2_error_bound(k: usize, t: usize) -> f64 {
+    let (k, t) = (k as f64, t as f64);
+    let max_m = (2.0 * (k - 1.0).sqrt()).floor() as usize;
+
+    // log2(2^a + 2^b) without leaving the logarithms.
+    let log2_sum = |a: f64, b: f64| a.max(b) + (1.0 + 2f64.powf(-(a - b).abs())).log2();
+
+    let mut best = f64::INFINITY;
+    for big_m in 3..=max_m {
+        // p_(k,t) = 2.00743 ln(2) k 2^-k (2^(k-2-Mt) + 8(π^2-6)/3 2^(k-2) S), where
+        // S = Σ_(m=3..M) Σ_(j=2..m) 2^(m-(m-1)t-j-(k-1)/j). The factors 2^-k and 2^(k-2) are merged.
+        let mut sum = f64::NEG_INFINITY;
+        for m in 3..=big_m {
+            for j in 2..=m {
+                let (m, j) = (m as f64, j as f64);
+                sum = log2_sum(sum, m - (m - 1.0) * t - j - (k - 1.0) / j);
+            }
+        }
+
+        let constant = (8.0 * (std::f64::consts::PI.powi(2) - 6.0) / 3.0).log2();
+        let bound = (2.00743 * std::f64::consts::LN_2 * k).log2()
+            + log2_sum(-2.0 - big_m as f64 * t, constant - 2.0 + sum);
+        best = best.min(bound);
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod test_miller_rabin_rounds {
+    use super::*;
+
+    #[test]
+    fn fips_186_4_table_c_3() {
+        // Rounds for the primes of 1024, 2048 and 3072-bit keys, without a Lucas test.
+        assert_eq!(miller_rabin_rounds(512, 100), 7);
+        assert_eq!(miller_rabin_rounds(1024, 112), 5);
+        assert_eq!(miller_rabin_rounds(1536, 128), 4);
+    }
+
+    #[test]
+    fn fewer_rounds_for_larger_numbers() {
+        let rounds: Vec<usize> = [256, 512, 1024, 1536, 2048]
+            .iter()
+            .map(|&bits| miller_rabin_rounds(bits, 128))
+            .collect();
+        assert_eq!(rounds, vec![27, 12, 6, 4, 3]);
+        assert_eq!(miller_rabin_rounds(1024, 80), 3);
+    }
+
+    #[test]
+    fn never_more_than_the_worst_case() {
+        for &bits in &[0, 5, 16, 64, 100, 4096] {
+            for &security_bits in &[0, 1, 80, 128, 256] {
+                let rounds = miller_rabin_rounds(bits, security_bits);
+                assert!(rounds >= 1);
+                assert!(rounds <= cmp::max(1, (security_bits as usize).div_ceil(2)));
+            }
+        }
+
+        // Too small for the average case bound.
+        assert_eq!(miller_rabin_rounds(5, 80), 40);
+    }
+
+    #[test]
+    fn includes_the_largest_m() {
+        // M may reach 2 sqrt(k - 1), so 4-bit numbers have a bound, with M = 3 as the only choice.
+        assert_eq!(log2_error_bound(3, 1), f64::INFINITY);
+        assert!((log2_error_bound(4, 1) - 2.1721).abs() < 1e-4);
+    }
+}
+
+lazy_static! {
+    /// the small primes as BigInts.
+    static ref SMALL_PRIME_NUMBERS: Vec<BigInt> =
+        SMALL_PRIMES.iter().map(|x| x.to_bigint().unwrap()).collect();
+}
+
+/// n modulo each of the small primes, worked out from the 32-bit digits of n with u64 arithmetic instead of a
+/// BigInt division per prime. The sign of n is ignored.
+fn small_prime_remainders(n: &BigInt) -> Vec<u32> {
+    let (_, digits) = n.to_u32_digits();
+    SMALL_PRIMES
+        .iter()
+        .map(|&prime| {
+            let prime = prime as u64;
+            let remainder = digits.iter().rev().fold(0, |remainder, &digit| {
+                ((remainder << 32) | digit as u64) % prime
+            });
+            remainder as u32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_small_prime_remainders {
+    use super::*;
+
+    #[test]
+    fn match_bigint_remainders() {
+        let n = string_to_number("340282366920938463463374607431768211457123456789");
+        let remainders = small_prime_remainders(&n);
+        assert_eq!(remainders.len(), SMALL_PRIMES.len());
+
+        for (remainder, prime) in remainders.iter().zip(SMALL_PRIME_NUMBERS.iter()) {
+            assert_eq!(remainder.to_bigint().unwrap(), &n % prime);
+        }
+    }
+}
+
+/// settles whether n is prime by trial division by the small primes, if that is enough: Some(false) for numbers
+/// below 2 and multiples of a small prime, Some(true) for the small primes themselves, and None otherwise.
+fn trial_division(n: &BigInt) -> Option<bool> {
+    if *n < *TWO {
+        return Some(false);
+    }
+
+    if SMALL_PRIME_NUMBERS.contains(n) {
+        return Some(true);
+    }
+
+    if small_prime_remainders(n).contains(&0) {
+        return Some(false);
+    }
+
+    None
+}
+
+/// determines whether a given number is prime by first running some simple primality tests through a small set of
+/// known primes and bases, and then, if all these basic tests pass, returns the result of the Miller-Rabin test with
+/// `WORST_CASE_ROUNDS` rounds. Fails if the seed is shorter than 32 bytes.
+///
+/// ## Reference
+/// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
+pub fn is_prime(n: &BigInt, seed: &[u8]) -> Result<bool, CryptoError> {
+    is_prime_with_rounds(n, WORST_CASE_ROUNDS, seed)
+}
+
+/// determines whether a given number is prime like `is_prime`, with the given number of Miller-Rabin rounds. Fails
+/// if the seed is shorter than 32 bytes.
+pub fn is_prime_with_rounds(n: &BigInt, rounds: usize, seed: &[u8]) -> Result<bool, CryptoError> {
+    // Check the seed up front, so that short seeds fail the same way for every n.
+    from_slice(seed)?;
+
+    if let Some(answer) = trial_division(n) {
+        return Ok(answer);
+    }
+
+    let bases_as_bigints: Vec<BigInt> = BASES.iter().map(|x| x.to_bigint().unwrap()).collect();
+
+    // n is odd and above 1000 by now, so the context always exists.
+    let ring = match MontgomeryContext::new(n) {
+        Some(ring) => ring,
+        None => return Ok(false),
+    };
+
+    for base in &bases_as_bigints {
+        if !is_fermat_probable_prime(&ring, base) {
+            return Ok(false);
+        }
+    }
+
+    miller_rabin_with_rounds(n, rounds, seed)
+}
+
+#[cfg(test)]
+mod test_is_prime_and_rabin_miller {
+    use super::*;
+
+    #[test]
+    fn miniscule_prime() {
+        let a = &string_to_number("3");
+        let expected = true;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn miniscule_not_prime() {
+        let a = &string_to_number("4");
+        let expected = false;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn tiny_prime() {
+        let a = &string_to_number("1049");
+        let expected = true;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn tiny_not_prime() {
+        let a = &string_to_number("1050");
+        let expected = false;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn small_prime() {
+        let a = &string_to_number("100103");
+        let expected = true;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn small_not_prime() {
+        let a = &string_to_number("100105");
+        let expected = false;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn medium_prime() {
+        let a = &string_to_number("100000015333");
+        let expected = true;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn medium_not_prime() {
+        let a = &string_to_number("100000015334");
+        let expected = false;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn large_prime() {
+        let a = &string_to_number("335184372088831");
+        let expected = true;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn large_not_prime() {
+        let a = &string_to_number("335184372088832");
+        let expected = false;
+        assert_eq!(is_prime(a, test_seed()), Ok(expected));
+    }
+
+    #[test]
+    fn same_witnesses_in_both_rings() {
+        // 3215031751 = 151 · 751 · 28351 is a strong pseudoprime to the bases 2, 3, 5 and 7, but not to 11.
+        let n = string_to_number("3215031751");
+        let (d, s) = (string_to_number("1607515875"), 1);
+        let montgomery = MontgomeryContext::new(&n).unwrap();
+        let barrett = BarrettContext::new(&n).unwrap();
+
+        for &(base, expected) in &[(2, false), (3, false), (5, false), (7, false), (11, true)] {
+            let base = BigInt::from(base);
+            assert_eq!(is_witness(&montgomery, &base, &d, s), expected);
+            assert_eq!(is_witness(&barrett, &base, &d, s), expected);
+        }
+
+        // The Carmichael number 561 = 3 · 11 · 17 fools Fermat's test for every base coprime to it.
+        let n = string_to_number("561");
+        let montgomery = MontgomeryContext::new(&n).unwrap();
+        let barrett = BarrettContext::new(&n).unwrap();
+        for &(base, expected) in &[(2, true), (3, false), (5, true)] {
+            let base = BigInt::from(base);
+            assert_eq!(is_fermat_probable_prime(&montgomery, &base), expected);
+            assert_eq!(is_fermat_probable_prime(&barrett, &base), expected);
+        }
+    }
+
+    #[test]
+    fn deterministic_bounds_are_tight() {
+        // Each bound fools all of its own bases, and the next set of bases catches it.
+        for (i, &(bound, bases)) in DETERMINISTIC_BOUNDS.iter().enumerate() {
+            let n = string_to_number(bound);
+            let ring = MontgomeryContext::new(&n).unwrap();
+            let (d, s) = odd_part(&(&n - &*ONE));
+            for &base in &SMALL_PRIMES[..bases] {
+                assert!(!is_witness(&ring, &BigInt::from(base), &d, s));
+            }
+
+            let expected = if i + 1 < DETERMINISTIC_BOUNDS.len() {
+                Some(false)
+            } else {
+                None
+            };
+            assert_eq!(deterministic_miller_rabin(&n), expected, "{}", bound);
+        }
+    }
+
+    #[test]
+    fn deterministic_bases_by_size() {
+        let bases = |n: &str| deterministic_bases(&string_to_number(n));
+        assert_eq!(bases("0"), Some(&[2][..]));
+        assert_eq!(bases("2046"), Some(&[2][..]));
+        assert_eq!(bases("2047"), Some(&[2, 3][..]));
+        assert_eq!(bases("18446744073709551615").map(|b| b.len()), Some(12));
+        assert_eq!(
+            bases("3317044064679887385961980").map(|b| b.len()),
+            Some(13)
+        );
+        assert_eq!(bases("3317044064679887385961981"), None);
+    }
+
+    #[test]
+    fn deterministic_miller_rabin_matches_baillie_psw() {
+        for n in 0..5000 {
+            let n = BigInt::from(n);
+            assert_eq!(deterministic_miller_rabin(&n), Some(baillie_psw(&n)));
+        }
+
+        // The largest prime below 2^64, and 2^64 - 1 = 3 · 5 · 17 · 257 · 641 · 65537 · 6700417.
+        let prime = string_to_number("18446744073709551557");
+        assert_eq!(deterministic_miller_rabin(&prime), Some(true));
+        let composite = string_to_number("18446744073709551615");
+        assert_eq!(deterministic_miller_rabin(&composite), Some(false));
+    }
+
+    #[test]
+    fn seed_independent_for_small_numbers() {
+        for n in &[
+            "3",
+            "4",
+            "1049",
+            "1050",
+            "100103",
+            "100105",
+            "100000015333",
+            "100000015334",
+            "335184372088831",
+            "335184372088832",
+            "3215031751",
+        ] {
+            let n = string_to_number(n);
+            let expected = deterministic_miller_rabin(&n).unwrap();
+            for seed in &[[0; 32], [1; 32], [7; 32], [0xff; 32]] {
+                assert_eq!(is_prime(&n, seed), Ok(expected));
+                assert_eq!(miller_rabin(&n, seed), Ok(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn short_seed() {
+        let a = &string_to_number("1049");
+        assert_eq!(is_prime(a, &[1; 31]), Err(CryptoError::SeedTooShort));
+        assert_eq!(miller_rabin(a, &[1; 31]), Err(CryptoError::SeedTooShort));
+    }
+}
+
+/// the Jacobi symbol (a/n) for an odd positive n: 0 if a and n have a common factor, and otherwise 1 or -1. For a
+/// prime n it is -1 exactly when a is not a square modulo n. Returns None if n is even or not positive.
+///
+/// ## Reference
+/// See algorithm 2.3.5 of Crandall and Pomerance, Prime Numbers: A Computational Perspective.
+pub fn jacobi(a: &BigInt, n: &BigInt) -> Option<i32> {
+    if *n <= *ZERO || n % &*TWO == *ZERO {
+        return None;
+    }
+
+    let mut a = a % n;
+    if a < *ZERO {
+        a += n;
+    }
+    let mut n = n.clone();
+    let mut result = 1;
+
+    // (2/n) is -1 for n = 3 or 5 mod 8, and swapping a and n flips the sign when both are 3 mod 4.
+    let mod_eight = |x: &BigInt| (x % 8u32).to_u32().unwrap();
+    while a != *ZERO {
+        while &a % &*TWO == *ZERO {
+            a /= &*TWO;
+            if mod_eight(&n) == 3 || mod_eight(&n) == 5 {
+                result = -result;
+            }
+        }
+
+        std::mem::swap(&mut a, &mut n);
+        if mod_eight(&a) % 4 == 3 && mod_eight(&n) % 4 == 3 {
+            result = -result;
+        }
+        a = &a % &n;
+    }
+
+    Some(if n == *ONE { result } else { 0 })
+}
+
+#[cfg(test)]
+mod test_jacobi {
+    use super::*;
+
+    #[test]
+    fn known_values() {
+        let cases = [
+            (0, 1, 1),
+            (0, 3, 0),
+            (1, 1, 1),
+            (2, 15, 1),
+            (5, 21, 1),
+            (8, 21, -1),
+            (19, 45, 1),
+            (21, 45, 0),
+            (1001, 9907, -1),
+            (5, 3233, -1),
+            (-7, 3233, -1),
+            (-1, 7, -1),
+        ];
+        for &(a, n, expected) in &cases {
+            assert_eq!(
+                jacobi(&BigInt::from(a), &BigInt::from(n)),
+                Some(expected),
+                "({}/{})",
+                a,
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_even_and_negative_n() {
+        for &n in &[0, 2, 10, -3] {
+            assert_eq!(jacobi(&ONE, &BigInt::from(n)), None);
+        }
+    }
+}
+
+/// the first D in 5, -7, 9, -11, 13, ... with (D/n) = -1, the parameter of the strong Lucas test after Selfridge.
+/// Returns None if some D shares a factor with n, which proves n composite unless n is |D| itself, and for
+/// perfect squares, for which no such D exists.
+fn selfridge_parameter(n: &BigInt) -> Option<BigInt> {
+    let root = n.sqrt();
+    if &root * &root == *n {
+        return None;
+    }
+
+    let mut d = BigInt::from(5);
+    loop {
+        match jacobi(&d, n)? {
+            -1 => return Some(d),
+            0 if d.abs() != *n => return None,
+            _ => {}
+        }
+
+        d = if d > *ZERO { -d - 2 } else { -d + 2 };
+    }
+}
+
+/// the strong Lucas probable prime test for the Lucas sequences with P = 1 and Q = (1 - D) / 4, computing in a
+/// ring modulo an odd n that is not a square and for which (D/n) = -1. Writing n + 1 = 2^s · k with k odd, a prime
+/// n has U_k = 0 or V_(k · 2^r) = 0 for some r < s.
+///
+/// ## Reference
+/// See section 3 of Baillie and Wagstaff, [Lucas Pseudoprimes](https://doi.org/10.1090/S0025-5718-1980-0583518-6),
+/// and appendix C.3.3 of [FIPS 186-4](https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.186-4.pdf).
+fn is_strong_lucas_probable_prime<R: ModRing>(ring: &R, d: &BigInt) -> bool {
+    let zero = ring.to_element(&ZERO);
+    let q = ring.to_element(&((&*ONE - d) / 4));
+    let d = ring.to_element(d);
+    let half = match ring.inverse(&ring.to_element(&TWO)) {
+        Some(half) => half,
+        None => return false,
+    };
+    let (k, s) = odd_part(&(ring.modulus() + &*ONE));
+
+    // U_1 = 1 and V_1 = P = 1. Walk the bits of k below the top one, doubling the index for each bit with
+    // U_2j = U_j · V_j and V_2j = V_j^2 - 2 Q^j, and stepping to j + 1 for a set bit with
+    // U_(j+1) = (P · U_j + V_j) / 2 and V_(j+1) = (D · U_j + P · V_j) / 2.
+    let (mut u, mut v, mut q_power) = (ring.one(), ring.one(), q.clone());
+    let bits: Vec<bool> = k
+        .to_bytes_be()
+        .1
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1))
+        .skip_while(|&bit| !bit)
+        .skip(1)
+        .collect();
+
+    for bit in bits {
+        u = ring.mul(&u, &v);
+        v = ring.sub(&ring.square(&v), &ring.add(&q_power, &q_power));
+        q_power = ring.square(&q_power);
+
+        if bit {
+            let next_u = ring.mul(&ring.add(&u, &v), &half);
+            v = ring.mul(&ring.add(&ring.mul(&d, &u), &v), &half);
+            u = next_u;
+            q_power = ring.mul(&q_power, &q);
+        }
+    }
+
+    if u == zero || v == zero {
+        return true;
+    }
+
+    for _ in 1..s {
+        v = ring.sub(&ring.square(&v), &ring.add(&q_power, &q_power));
+        q_power = ring.square(&q_power);
+        if v == zero {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// determines whether a given number is prime with the Baillie-PSW test: trial division, a strong probable prime
+/// test to base 2, and a strong Lucas probable prime test. No composite number is known to pass both, none exists
+/// below 2^64, and the test needs no randomness, so it gives the same answer every time. It costs about as much as
+/// three or four rounds of Miller-Rabin.
+///
+/// ## Reference
+/// See Baillie and Wagstaff, [Lucas Pseudoprimes](https://doi.org/10.1090/S0025-5718-1980-0583518-6), and Pomerance,
+/// Selfridge and Wagstaff, [The Pseudoprimes to 25 · 10^9](https://doi.org/10.1090/S0025-5718-1980-0572872-7).
+pub fn baillie_psw(n: &BigInt) -> bool {
+    if let Some(answer) = trial_division(n) {
+        return answer;
+    }
+
+    is_baillie_psw_probable_prime(n)
+}
+
+/// the base 2 and strong Lucas tests of `baillie_psw`, for an n that trial division has not settled.
+fn is_baillie_psw_probable_prime(n: &BigInt) -> bool {
+    // n is odd and above 1000 by now, so the context always exists.
+    let ring = match MontgomeryContext::new(n) {
+        Some(ring) => ring,
+        None => return false,
+    };
+
+    let (d, s) = odd_part(&(n - &*ONE));
+    if is_witness(&ring, &TWO, &d, s) {
+        return false;
+    }
+
+    match selfridge_parameter(n) {
+        Some(d) => is_strong_lucas_probable_prime(&ring, &d),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test_baillie_psw {
+    use super::*;
+    use rsa::test_keypair;
+
+    /// whether n is prime, by trial division up to its square root.
+    fn is_prime_by_trial_division(n: u32) -> bool {
+        n >= 2
+            && (2..)
+                .take_while(|i| i * i <= n)
+                .all(|i| !n.is_multiple_of(i))
+    }
+
+    #[test]
+    fn matches_trial_division() {
+        for n in 0..5000 {
+            assert_eq!(
+                baillie_psw(&BigInt::from(n)),
+                is_prime_by_trial_division(n),
+                "{}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn primes() {
+        let k = test_keypair();
+        let mersenne = |p: usize| (BigInt::from(1) << p) - 1;
+
+        for prime in &[
+            string_to_number("100000015333"),
+            string_to_number("335184372088831"),
+            mersenne(127),
+            mersenne(521),
+            k.p.clone(),
+            k.q.clone(),
+        ] {
+            assert!(baillie_psw(prime), "{}", prime);
+        }
+        assert!(!baillie_psw(&(&k.p * &k.q)));
+        assert!(!baillie_psw(&mersenne(128)));
+    }
+
+    #[test]
+    fn carmichael_numbers() {
+        // Composites that pass Fermat's test for every base coprime to them.
+        for n in &[
+            561, 1105, 1729, 2465, 2821, 6601, 8911, 41041, 825265, 321197185,
+        ] {
+            let n = BigInt::from(*n);
+            assert!(is_fermat_probable_prime(
+                &MontgomeryContext::new(&n).unwrap(),
+                &TWO
+            ));
+            assert!(!baillie_psw(&n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn strong_pseudoprimes() {
+        // Strong pseudoprimes to base 2, and one to every prime base up to 41.
+        for n in &[
+            "2047",
+            "3277",
+            "4033",
+            "4681",
+            "8321",
+            "3215031751",
+            "3317044064679887385961981",
+        ] {
+            let n = string_to_number(n);
+            let (d, s) = odd_part(&(&n - &*ONE));
+            assert!(!is_witness(
+                &MontgomeryContext::new(&n).unwrap(),
+                &TWO,
+                &d,
+                s
+            ));
+            assert!(!baillie_psw(&n), "{}", n);
+        }
+
+        // Every base up to 41 fails to prove the last one composite; the Lucas test does not.
+        let n = string_to_number("3317044064679887385961981");
+        let ring = MontgomeryContext::new(&n).unwrap();
+        let (d, s) = odd_part(&(&n - &*ONE));
+        for base in SMALL_PRIMES.iter().take_while(|&&p| p <= 41) {
+            assert!(!is_witness(&ring, &BigInt::from(*base), &d, s));
+        }
+    }
+
+    #[test]
+    fn strong_lucas_pseudoprimes() {
+        // Composites that pass the strong Lucas test but not the base 2 test, in both kinds of ring.
+        for &(n, d) in &[(5459, -7), (5777, 5), (10877, 5), (16109, 13), (18971, -11)] {
+            let (n, d) = (BigInt::from(n), BigInt::from(d));
+            assert_eq!(selfridge_parameter(&n), Some(d.clone()));
+            assert!(is_strong_lucas_probable_prime(
+                &MontgomeryContext::new(&n).unwrap(),
+                &d
+            ));
+            assert!(is_strong_lucas_probable_prime(
+                &BarrettContext::new(&n).unwrap(),
+                &d
+            ));
+            assert!(!baillie_psw(&n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn perfect_squares() {
+        for n in &["1018081", "1073676289", "10000000000000000000000000000"] {
+            assert_eq!(selfridge_parameter(&string_to_number(n)), None);
+            assert!(!baillie_psw(&string_to_number(n)));
+        }
+    }
+}
+
+/// the ways `is_prime_with` can test a number that trial division did not settle.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrimalityTest {
+    /// Fermat tests to the bases 2, 3, 5, 7 and 11, then 50 rounds of Miller-Rabin with bases drawn from the seed,
+    /// as in `is_prime`. The default.
+    #[default]
+    MillerRabin = 0,
+    /// the deterministic Baillie-PSW test, as in `baillie_psw`. The seed is not used.
+    BailliePsw = 1,
+}
+
+/// determines whether a given number is prime with the chosen test. For `PrimalityTest::MillerRabin`, rounds is
+/// the number of Miller-Rabin rounds: `WORST_CASE_ROUNDS` for any number, or `miller_rabin_rounds` for random
+/// candidates. Fails if the seed is shorter than 32 bytes, for every test.
+pub fn is_prime_with(
+    n: &BigInt,
+    seed: &[u8],
+    test: PrimalityTest,
+    rounds: usize,
+) -> Result<bool, CryptoError> {
+    match test {
+        PrimalityTest::MillerRabin => is_prime_with_rounds(n, rounds, seed),
+        PrimalityTest::BailliePsw => {
+            from_slice(seed)?;
+            Ok(baillie_psw(n))
+        }
+    }
+}
+
+impl PrimalityTest {
+    /// like `is_prime_with`, but without trial division, for an n that has no small factors and is not small itself.
+    fn is_probable_prime(
+        self,
+        n: &BigInt,
+        rounds: usize,
+        seed: &[u8],
+    ) -> Result<bool, CryptoError> {
+        match self {
+            PrimalityTest::MillerRabin => miller_rabin_with_rounds(n, rounds, seed),
+            PrimalityTest::BailliePsw => {
+                from_slice(seed)?;
+                Ok(is_baillie_psw_probable_prime(n))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_is_prime_with {
+    use super::*;
+
+    #[test]
+    fn tests_agree() {
+        for n in &["1049", "1050", "100000015333", "100000015334", "3215031751"] {
+            let n = string_to_number(n);
+            assert_eq!(
+                is_prime_with(
+                    &n,
+                    test_seed(),
+                    PrimalityTest::MillerRabin,
+                    WORST_CASE_ROUNDS
+                ),
+                is_prime_with(
+                    &n,
+                    test_seed(),
+                    PrimalityTest::BailliePsw,
+                    WORST_CASE_ROUNDS
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn baillie_psw_ignores_the_seed() {
+        let n = string_to_number("3317044064679887385961981");
+        for seed in &[[0; 32], [1; 32], [0xff; 32]] {
+            assert_eq!(
+                is_prime_with(&n, seed, PrimalityTest::BailliePsw, WORST_CASE_ROUNDS),
+                Ok(false)
+            );
+        }
+        assert_eq!(
+            is_prime_with(&n, &[1; 31], PrimalityTest::BailliePsw, WORST_CASE_ROUNDS),
+            Err(CryptoError::SeedTooShort)
+        );
+    }
+}
+
+/// odd candidates from a random start upwards, with their remainders modulo the odd small primes kept in machine
+/// words. Stepping to the next candidate adds 2 to every remainder, so candidates with a small factor are skipped
+/// without any BigInt arithmetic, and only the survivors need a primality test.
+///
+/// ## Reference
+/// See note 4.51 (ii) of the [Handbook of Applied Cryptography](https://cacr.uwaterloo.ca/hac/).
+struct Sieve {
+    /// the first candidate
+    start: BigInt,
+    /// how far the current candidate is past the start
+    offset: u64,
+    /// the offset at which the candidates leave the range
+    end: u64,
+    /// the current candidate modulo each odd small prime
+    remainders: Vec<u32>,
+}
+
+impl Sieve {
+    /// starts at the odd number nearest above start, stopping before limit.
+    fn new(start: BigInt, limit: &BigInt) -> Sieve {
+        let start = if &start % &*TWO == *ZERO {
+            start + 1
+        } else {
+            start
+        };
+
+        // The candidates are all odd, so only the odd small primes are kept.
+        let mut remainders = small_prime_remainders(&start);
+        remainders.remove(0);
+
+        Sieve {
+            end: (limit - &start).to_u64().unwrap_or(u64::MAX),
+            start,
+            offset: 0,
+            remainders,
+        }
+    }
+
+    /// the current candidate.
+    fn candidate(&self) -> BigInt {
+        &self.start + self.offset
+    }
+
+    /// whether the current candidate is out of range.
+    fn is_exhausted(&self) -> bool {
+        self.offset >= self.end
+    }
+
+    /// whether the current candidate is small enough to be one of the small primes, which the remainders would
+    /// wrongly rule out.
+    fn is_small(&self) -> bool {
+        self.start
+            .to_u64()
+            .is_some_and(|start| start + self.offset <= 1000)
+    }
+
+    /// whether the current candidate is divisible by one of the odd small primes.
+    fn has_small_factor(&self) -> bool {
+        self.remainders.contains(&0)
+    }
+
+    /// moves on to the next odd candidate.
+    fn advance(&mut self) {
+        self.offset += 2;
+        for (remainder, &prime) in self.remainders.iter_mut().zip(&SMALL_PRIMES[1..]) {
+            *remainder += 2;
+            if *remainder >= prime as u32 {
+                *remainder -= prime as u32;
+            }
+        }
+    }
+}
+
+/// the extra security bits for a search that tests up to the given number of candidates: log2 of that number,
+/// rounded up. `miller_rabin_rounds` bounds the error for a single candidate drawn uniformly at random, but the
+/// candidates of an incremental search are consecutive numbers from one start, and the search fails if any one of
+/// them fools the test. Each lies at a uniformly random place on its own, so by the union bound the error is at most
+/// the number of candidates tested times the bound for one.
+///
+/// ## Reference
+/// Brandt and Damgård, [On Generation of Probable Primes by Incremental
+/// Search](https://doi.org/10.1007/3-540-48071-4_26), prove tighter bounds for this kind of search; this margin
+/// does not rely on them.
+fn search_margin(tries: usize) -> u32 {
+    tries
+        .checked_next_power_of_two()
+        .map_or(usize::BITS, usize::trailing_zeros)
+}
+
+#[cfg(test)]
+mod test_search_margin {
+    use super::*;
+
+    #[test]
+    fn log2_of_the_tries() {
+        assert_eq!(search_margin(0), 0);
+        assert_eq!(search_margin(1), 0);
+        assert_eq!(search_margin(1000), 10);
+        assert_eq!(search_margin(1024), 10);
+        assert_eq!(search_margin(1025), 11);
+        assert_eq!(search_margin(usize::MAX), usize::BITS);
+    }
+
+    #[test]
+    fn more_rounds_for_a_search() {
+        // The primes of a 2048-bit key, with the default budget of 10 tries per bit.
+        assert_eq!(miller_rabin_rounds(1024, 128), 6);
+        assert_eq!(miller_rabin_rounds(1024, 128 + search_margin(10240)), 7);
+    }
+}
+
+/// searches [low, high) for a prime that passes the test and is suitable, examining odd candidates upwards from a
+/// random start, and from a new one whenever the candidates leave the range. Only candidates that get a primality
+/// test count as tries: those ruled out by a small factor cost next to nothing, and counting them would make the
+/// budget depend on how many the sieve removes. Fails if none is found within the given number of tries.
+fn search_prime<F: Fn(&BigInt) -> bool>(
+    low: &BigInt,
+    high: &BigInt,
+    tries: usize,
+    test: PrimalityTest,
+    rounds: usize,
+    seed: &[u8],
+    suitable: F,
+) -> Result<BigInt, CryptoError> {
+    let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+    let mut sieve: Option<Sieve> = None;
+
+    let mut tested = 0;
+
+    while tested < tries {
+        if sieve.as_ref().is_none_or(Sieve::is_exhausted) {
+            sieve = Some(Sieve::new(rng.gen_bigint_range(low, high), high));
+        }
+        let sieve = sieve.as_mut().unwrap();
+
+        if sieve.is_small() || !sieve.has_small_factor() {
+            tested += 1;
+
+            let candidate = sieve.candidate();
+            let is_prime = if sieve.is_small() {
+                is_prime_with(&candidate, seed, test, rounds)?
+            } else {
+                test.is_probable_prime(&candidate, rounds, seed)?
+            };
+
+            if is_prime && suitable(&candidate) {
+                return Ok(candidate);
+            }
+        }
+
+        sieve.advance();
+    }
+
+    Err(CryptoError::PrimeNotFound)
+}
+
+/// generates a random prime for a given seed. The search starts at a random number in [2^(bits-1), 2^bits) and
+/// walks upwards (see `Sieve`), testing candidates with Miller-Rabin at security_bits plus the margin of
+/// `search_margin`, so that the result is composite with probability at most about 2^-security_bits. Fails if no
+/// prime is found within the given number of tries, each try being one candidate without small factors.
+///
+/// ## Reference
+/// Ported from: [http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt](http://www.maths.dk/teaching/courses/math398-spring2017/code/cryptomath.txt)
+pub fn generate_prime(
+    bits: usize,
+    tries: usize,
+    security_bits: u32,
+    seed: &[u8],
+) -> Result<BigInt, CryptoError> {
+    let rounds = miller_rabin_rounds(bits, security_bits.saturating_add(search_margin(tries)));
+    let bits_minus_one = bits - 1;
+    let x = pow(TWO.clone(), bits_minus_one);
+    let y = &*TWO * &x;
+
+    search_prime(
+        &x,
+        &y,
+        tries,
+        PrimalityTest::MillerRabin,
+        rounds,
+        seed,
+        |_| true,
+    )
+}
+
+#[cfg(test)]
+mod test_generate_prime {
+    use super::*;
+
+    /// the random start of the search for a prime of the given size from test_seed.
+    fn start(bits: usize) -> BigInt {
+        let x = pow(TWO.clone(), bits - 1);
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(test_seed()).unwrap());
+        rng.gen_bigint_range(&x, &(&*TWO * &x))
+    }
+
+    /// the prime that the search for a prime of the given size from test_seed should find: the first one from the
+    /// random start upwards.
+    fn next_prime_from_start(bits: usize) -> BigInt {
+        let mut n = start(bits);
+
+        while !is_prime(&n, test_seed()).unwrap() {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn miniscule_prime() {
+        let prime = generate_prime(2, 1000, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(prime, Ok(string_to_number("3")));
+    }
+
+    #[test]
+    fn tiny_prime() {
+        let prime = generate_prime(8, 1000, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(prime, Ok(string_to_number("191")));
+        assert_eq!(prime, Ok(next_prime_from_start(8)));
+    }
+
+    #[test]
+    fn medium_prime() {
+        let prime = generate_prime(64, 1000, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(prime, Ok(string_to_number("13851427945621599533")));
+        assert_eq!(prime, Ok(next_prime_from_start(64)));
+    }
+
+    #[test]
+    fn large_prime() {
+        let prime = generate_prime(256, 1000, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(
+            prime,
+            Ok(string_to_number(
+                "114141178859372032005452509386700108065494928407718354791631347197844820826787"
+            ))
+        );
+        assert_eq!(prime, Ok(next_prime_from_start(256)));
+    }
+
+    #[test]
+    fn security_level_does_not_change_the_prime() {
+        // Fewer rounds only make accepting a composite likelier, and none of these candidates fool them.
+        let prime = generate_prime(512, 1000, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(generate_prime(512, 1000, 80, test_seed()), prime);
+        assert_eq!(generate_prime(512, 1000, 256, test_seed()), prime);
+    }
+
+    #[test]
+    fn runs_out_of_tries() {
+        let prime = generate_prime(64, 0, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(prime, Err(CryptoError::PrimeNotFound));
+    }
+
+    #[test]
+    fn only_tested_candidates_count() {
+        // The odd candidates up to the prime, less those with a small factor.
+        let prime = next_prime_from_start(256);
+        let mut candidate = start(256) | BigInt::from(1);
+        let mut tested = 0;
+        while candidate <= prime {
+            if trial_division(&candidate) != Some(false) {
+                tested += 1;
+            }
+            candidate += 2;
+        }
+        assert!(tested < (&prime - start(256)).to_usize().unwrap() / 4);
+
+        let search = |tries| generate_prime(256, tries, DEFAULT_SECURITY_BITS, test_seed());
+        assert_eq!(search(tested), Ok(prime));
+        assert_eq!(search(tested - 1), Err(CryptoError::PrimeNotFound));
+    }
+
+    #[test]
+    fn short_seed() {
+        let prime = generate_prime(64, 1000, DEFAULT_SECURITY_BITS, &[1; 16]);
+        assert_eq!(prime, Err(CryptoError::SeedTooShort));
+    }
+}
+
+#[cfg(test)]
+mod test_sieve {
+    use super::*;
+
+    #[test]
+    fn remainders_follow_the_candidates() {
+        let start = string_to_number("123456789123456789123456789");
+        let limit = &start * &*TWO;
+        let mut sieve = Sieve::new(start.clone(), &limit);
+        assert_eq!(sieve.candidate(), start);
+
+        for _ in 0..1000 {
+            let candidate = sieve.candidate();
+            for (remainder, prime) in sieve.remainders.iter().zip(&SMALL_PRIME_NUMBERS[1..]) {
+                assert_eq!(BigInt::from(*remainder), &candidate % prime);
+            }
+            assert_eq!(
+                sieve.has_small_factor(),
+                trial_division(&candidate) == Some(false)
+            );
+            sieve.advance();
+        }
+    }
+
+    #[test]
+    fn starts_odd_and_stops_at_the_limit() {
+        let mut sieve = Sieve::new(string_to_number("1000000"), &string_to_number("1000006"));
+        assert_eq!(sieve.candidate(), string_to_number("1000001"));
+        assert!(!sieve.is_small());
+
+        for _ in 0..2 {
+            sieve.advance();
+            assert!(!sieve.is_exhausted());
+        }
+        sieve.advance();
+        assert_eq!(sieve.candidate(), string_to_number("1000007"));
+        assert!(sieve.is_exhausted());
+    }
+
+    #[test]
+    fn small_candidates() {
+        let mut sieve = Sieve::new(string_to_number("997"), &string_to_number("2000"));
+        // 997 is itself one of the small primes.
+        assert!(sieve.is_small() && sieve.has_small_factor());
+        sieve.advance();
+        assert!(sieve.is_small());
+        sieve.advance();
+        assert_eq!(sieve.candidate(), string_to_number("1001"));
+        assert!(!sieve.is_small());
+    }
+}
+
+/// generates a random prime for use as an RSA factor, checking candidates with the chosen primality test. With
+/// `PrimalityTest::MillerRabin`, the result is composite with probability at most about 2^-security_bits, as in
+/// `generate_prime`; Baillie-PSW has no such parameter. Candidates are searched for in [3 * 2^(bits-2), 2^bits) as
+/// in `generate_prime`, so that the product of two such primes has exactly 2 * bits bits, and primes p with
+/// gcd(e, p - 1) != 1 are skipped so that the public exponent e stays invertible. Fails if no such prime is found
+/// within the given number of tries, each try being one candidate without small factors.
+pub fn generate_rsa_prime(
+    bits: usize,
+    tries: usize,
+    e: &BigInt,
+    test: PrimalityTest,
+    security_bits: u32,
+    seed: &[u8],
+) -> Result<BigInt, CryptoError> {
+    let rounds = miller_rabin_rounds(bits, security_bits.saturating_add(search_margin(tries)));
+    let x = pow(TWO.clone(), bits - 2) * 3;
+    let y = pow(TWO.clone(), bits);
+
+    search_prime(&x, &y, tries, test, rounds, seed, |n| {
+        gcd(e, &(n - &*ONE)) == *ONE
+    })
+}
+
+#[cfg(test)]
+mod test_generate_rsa_prime {
+    use super::*;
+
+    #[test]
+    fn top_two_bits_set() {
+        let e = string_to_number("65537");
+        let prime = generate_rsa_prime(
+            128,
+            1000,
+            &e,
+            PrimalityTest::MillerRabin,
+            DEFAULT_SECURITY_BITS,
+            test_seed(),
+        )
+        .unwrap();
+        let lower = pow(TWO.clone(), 126) * 3;
+        assert!(prime >= lower);
+        assert_eq!(prime.bits(), 128);
+    }
+
+    #[test]
+    fn coprime_to_exponent() {
+        let e = string_to_number("3");
+        let prime = generate_rsa_prime(
+            128,
+            1000,
+            &e,
+            PrimalityTest::MillerRabin,
+            DEFAULT_SECURITY_BITS,
+            test_seed(),
+        )
+        .unwrap();
+        assert_eq!(gcd(&e, &(&prime - &*ONE)), *ONE);
+    }
+
+    #[test]
+    fn with_baillie_psw() {
+        let e = string_to_number("65537");
+        let prime = generate_rsa_prime(
+            512,
+            1000,
+            &e,
+            PrimalityTest::BailliePsw,
+            DEFAULT_SECURITY_BITS,
+            test_seed(),
+        )
+        .unwrap();
+        assert_eq!(prime.bits(), 512);
+        assert_eq!(is_prime(&prime, test_seed()), Ok(true));
+    }
+
+    #[test]
+    fn runs_out_of_tries() {
+        let e = string_to_number("65537");
+        assert_eq!(
+            generate_rsa_prime(
+                128,
+                0,
+                &e,
+                PrimalityTest::MillerRabin,
+                DEFAULT_SECURITY_BITS,
+                test_seed()
+            ),
+            Err(CryptoError::PrimeNotFound)
+        );
+    }
+}
+
+/// converts a vector of bytes to an array of u8's, for seeding a random number generator. Only the first 32 bytes
+/// are used; fewer is an error.
+///
+/// ## Reference
+/// Ref: https://stackoverflow.com/questions/29570607/is-there-a-good-way-to-convert-a-vect-to-an-array
+pub(crate) fn from_slice(bytes: &[u8]) -> Result<[u8; 32], CryptoError> {
+    let mut array = [0; 32];
+    let bytes = bytes.get(..array.len()).ok_or(CryptoError::SeedTooShort)?;
+    array.copy_from_slice(bytes);
+    Ok(array)
+}
+
+#[cfg(test)]
+mod test_from_slice {
+    use super::*;
+
+    #[test]
+    fn uses_first_32_bytes() {
+        let seed: Vec<u8> = (0..40).collect();
+        let expected: Vec<u8> = (0..32).collect();
+        assert_eq!(from_slice(&seed).unwrap().to_vec(), expected);
+    }
+
+    #[test]
+    fn short_seed() {
+        assert_eq!(from_slice(&[1; 31]), Err(CryptoError::SeedTooShort));
+        assert_eq!(from_slice(&[]), Err(CryptoError::SeedTooShort));
+    }
 }
 
 // Fixes: https://github.com/ColbyCypherSociety/ChatDemo/issues/21
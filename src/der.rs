@@ -0,0 +1,280 @@
+use crypto_math::bytes_to_number;
+use num::bigint::BigInt;
+
+/// tag of an ASN.1 INTEGER.
+pub const INTEGER: u8 = 0x02;
+/// tag of an ASN.1 BIT STRING.
+pub const BIT_STRING: u8 = 0x03;
+/// tag of an ASN.1 OCTET STRING.
+pub const OCTET_STRING: u8 = 0x04;
+/// tag of an ASN.1 NULL.
+pub const NULL: u8 = 0x05;
+/// tag of an ASN.1 OBJECT IDENTIFIER.
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+/// tag of an ASN.1 SEQUENCE (always constructed).
+pub const SEQUENCE: u8 = 0x30;
+
+/// the DER encoding of the rsaEncryption object identifier, 1.2.840.113549.1.1.1.
+pub static RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// encodes a single DER element (tag, length and contents).
+///
+/// ## Reference
+/// See [X.690, section 8.1](https://www.itu.int/rec/T-REC-X.690) and the DER rules in section 10.1.
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = contents.len();
+
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .iter()
+            .cloned()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x80 | len_bytes.len() as u8);
+        out.extend_from_slice(&len_bytes);
+    }
+
+    out.extend_from_slice(contents);
+    out
+}
+
+/// encodes a BigInt as a DER INTEGER (minimal two's complement, big-endian).
+pub fn encode_integer(num: &BigInt) -> Vec<u8> {
+    encode(INTEGER, &num.to_signed_bytes_be())
+}
+
+/// encodes the concatenation of already encoded elements as a DER SEQUENCE.
+pub fn encode_sequence(elements: &[Vec<u8>]) -> Vec<u8> {
+    encode(SEQUENCE, &elements.concat())
+}
+
+#[cfg(test)]
+mod test_encode {
+    use super::*;
+    use crypto_math::string_to_number;
+
+    #[test]
+    fn short_and_long_lengths() {
+        assert_eq!(encode(OCTET_STRING, &[1, 2]), vec![0x04, 0x02, 1, 2]);
+        assert_eq!(&encode(OCTET_STRING, &[0; 0x80])[..3], &[0x04, 0x81, 0x80]);
+        assert_eq!(
+            &encode(OCTET_STRING, &[0; 0x100])[..4],
+            &[0x04, 0x82, 0x01, 0x00]
+        );
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            encode_integer(&string_to_number("0")),
+            vec![0x02, 0x01, 0x00]
+        );
+        assert_eq!(
+            encode_integer(&string_to_number("127")),
+            vec![0x02, 0x01, 0x7f]
+        );
+        assert_eq!(
+            encode_integer(&string_to_number("128")),
+            vec![0x02, 0x02, 0x00, 0x80]
+        );
+        assert_eq!(
+            encode_integer(&string_to_number("65537")),
+            vec![0x02, 0x03, 0x01, 0x00, 0x01]
+        );
+        assert_eq!(
+            encode_integer(&string_to_number("-129")),
+            vec![0x02, 0x02, 0xff, 0x7f]
+        );
+    }
+
+    #[test]
+    fn sequences() {
+        let one = encode_integer(&string_to_number("1"));
+        let two = encode_integer(&string_to_number("2"));
+        assert_eq!(
+            encode_sequence(&[one, two]),
+            vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]
+        );
+    }
+}
+
+/// reads DER elements one after another from a byte string. Every method returns None on malformed or non-DER
+/// input (for example lengths or integers that are not minimally encoded).
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// starts reading at the beginning of input.
+    pub fn new(input: &'a [u8]) -> Reader<'a> {
+        Reader { input }
+    }
+
+    /// true once every byte has been read.
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// the tag of the next element, without reading it.
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.input.first().cloned()
+    }
+
+    /// reads the next element, which must have the given tag, and returns its contents.
+    pub fn read(&mut self, tag: u8) -> Option<&'a [u8]> {
+        if self.input.len() < 2 || self.input[0] != tag {
+            return None;
+        }
+
+        let (len, header_len) = match self.input[1] {
+            len if len < 0x80 => (len as usize, 2),
+            0x80 => return None,
+            first => {
+                let count = (first & 0x7f) as usize;
+                let len_bytes = self.input.get(2..2 + count)?;
+
+                if count > std::mem::size_of::<usize>() || len_bytes[0] == 0 {
+                    return None;
+                }
+
+                let len = len_bytes.iter().fold(0, |acc, &b| (acc << 8) | b as usize);
+                if len < 0x80 {
+                    return None;
+                }
+
+                (len, 2 + count)
+            }
+        };
+
+        let contents = self.input.get(header_len..header_len.checked_add(len)?)?;
+        self.input = &self.input[header_len + len..];
+        Some(contents)
+    }
+
+    /// reads a SEQUENCE and returns a reader over its elements.
+    pub fn read_sequence(&mut self) -> Option<Reader<'a>> {
+        self.read(SEQUENCE).map(Reader::new)
+    }
+
+    /// reads a non-negative INTEGER. Negative integers are rejected, since no key format here uses them.
+    pub fn read_integer(&mut self) -> Option<BigInt> {
+        let contents = self.read(INTEGER)?;
+
+        match contents {
+            [] => None,
+            [first, ..] if first & 0x80 != 0 => None,
+            [0, second, ..] if second & 0x80 == 0 => None,
+            _ => Some(bytes_to_number(contents)),
+        }
+    }
+
+    /// reads a NULL.
+    pub fn read_null(&mut self) -> Option<()> {
+        match self.read(NULL)? {
+            [] => Some(()),
+            _ => None,
+        }
+    }
+
+    /// reads a BIT STRING made of whole bytes and returns those bytes.
+    pub fn read_bit_string(&mut self) -> Option<&'a [u8]> {
+        match self.read(BIT_STRING)? {
+            [0, rest @ ..] => Some(rest),
+            _ => None,
+        }
+    }
+
+    /// reads the contents of the next element and checks that nothing follows it.
+    pub fn finish<T, F>(mut self, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Option<T>,
+    {
+        let value = f(&mut self)?;
+
+        if self.is_empty() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_reader {
+    use super::*;
+    use crypto_math::string_to_number;
+
+    #[test]
+    fn reads_what_encode_writes() {
+        let seven = string_to_number("7");
+        let big = string_to_number("123456789123456789123456789123456789");
+        let der = encode_sequence(&[
+            encode_integer(&seven),
+            encode_integer(&big),
+            encode(NULL, &[]),
+            encode(BIT_STRING, &[0, 1, 2]),
+        ]);
+
+        let mut reader = Reader::new(&der);
+        let mut sequence = reader.read_sequence().unwrap();
+        assert!(reader.is_empty());
+
+        assert_eq!(sequence.read_integer(), Some(seven));
+        assert_eq!(sequence.read_integer(), Some(big));
+        assert_eq!(sequence.read_null(), Some(()));
+        assert_eq!(sequence.read_bit_string(), Some(&[1, 2][..]));
+        assert!(sequence.is_empty());
+    }
+
+    #[test]
+    fn long_lengths() {
+        let der = encode(OCTET_STRING, &[9; 300]);
+        assert_eq!(Reader::new(&der).read(OCTET_STRING), Some(&[9; 300][..]));
+    }
+
+    #[test]
+    fn rejects_wrong_tag() {
+        assert_eq!(Reader::new(&[0x02, 0x01, 0x01]).read(NULL), None);
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        assert_eq!(Reader::new(&[0x04, 0x03, 1, 2]).read(OCTET_STRING), None);
+        assert_eq!(Reader::new(&[0x04, 0x82, 0x01]).read(OCTET_STRING), None);
+        assert_eq!(Reader::new(&[0x04]).read(OCTET_STRING), None);
+    }
+
+    #[test]
+    fn rejects_non_minimal_lengths() {
+        assert_eq!(
+            Reader::new(&[0x04, 0x81, 0x01, 0x00]).read(OCTET_STRING),
+            None
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x82, 0x00, 0x81]).read(OCTET_STRING),
+            None
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x80, 0x00, 0x00]).read(OCTET_STRING),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_bad_integers() {
+        assert_eq!(Reader::new(&[0x02, 0x00]).read_integer(), None);
+        assert_eq!(Reader::new(&[0x02, 0x01, 0x80]).read_integer(), None);
+        assert_eq!(Reader::new(&[0x02, 0x02, 0x00, 0x01]).read_integer(), None);
+    }
+
+    #[test]
+    fn finish_rejects_trailing_data() {
+        let reader = Reader::new(&[0x05, 0x00, 0x05, 0x00]);
+        assert_eq!(reader.finish(|r| r.read_null()), None);
+    }
+}
@@ -0,0 +1,121 @@
+use js_sys;
+use std::error::Error;
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+/// the ways an operation in this crate can fail. In JavaScript these are thrown as an `Error` named "CryptoError",
+/// whose `code` property holds the variant name (e.g. "MalformedKey") so that callers can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoError {
+    /// a seed has fewer than the 32 bytes needed to seed the random number generator.
+    SeedTooShort,
+    /// no prime was found within the allowed number of tries.
+    PrimeNotFound,
+    /// the public exponent is not a usable RSA exponent (odd, at least 3 and invertible for the key).
+    InvalidExponent,
+    /// the requested modulus size is not one of `rsa::SUPPORTED_MODULUS_BITS`.
+    UnsupportedModulusSize,
+    /// a key could not be parsed.
+    MalformedKey,
+    /// a key is well formed but of a type or protection this crate does not support.
+    UnsupportedKey,
+    /// the components of a key do not belong together, e.g. n is not p * q.
+    InvalidKey,
+    /// the key is too small for the chosen padding scheme.
+    KeyTooSmall,
+    /// a ciphertext is not a comma separated list of decimal numbers below the modulo.
+    MalformedCiphertext,
+    /// decryption failed because of a wrong key, a wrong password or tampered data. The reason is deliberately not
+    /// told apart.
+    DecryptionFailed,
+    /// a framed message is addressed to another key.
+    WrongRecipient,
+    /// a message must have at least one and at most `hybrid::MAX_RECIPIENTS` recipients.
+    InvalidRecipients,
+    /// a message is too long to be encrypted in one piece.
+    MessageTooLong,
+    /// an envelope was made by a newer (or unknown) version of the format.
+    UnsupportedVersion,
+    /// a message decrypted fine, but is not valid UTF-8 text.
+    InvalidUtf8,
+    /// the key is encrypted, but no password was given.
+    PasswordRequired,
+    /// the private key operation gave a wrong result, e.g. because of a hardware fault, so nothing was output.
+    FaultDetected,
+    /// a private key operation needs fresh randomness for blinding, see `rsa::Keypair::add_blinding_entropy`.
+    EntropyRequired,
+}
+
+impl CryptoError {
+    /// the variant name, used as the `code` of the JavaScript exception.
+    pub fn code(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            CryptoError::SeedTooShort => "seed must be at least 32 bytes long",
+            CryptoError::PrimeNotFound => "no prime found within the allowed number of tries",
+            CryptoError::InvalidExponent => "public exponent is not valid for this key",
+            CryptoError::UnsupportedModulusSize => {
+                "modulus size must be 1024, 2048, 3072 or 4096 bits"
+            }
+            CryptoError::MalformedKey => "key could not be parsed",
+            CryptoError::UnsupportedKey => "key type or key protection is not supported",
+            CryptoError::InvalidKey => "key components are inconsistent",
+            CryptoError::KeyTooSmall => "key is too small for this padding scheme",
+            CryptoError::MalformedCiphertext => {
+                "ciphertext is malformed or out of range for this key"
+            }
+            CryptoError::DecryptionFailed => "decryption failed",
+            CryptoError::WrongRecipient => "message is addressed to another key",
+            CryptoError::InvalidRecipients => "a message needs between 1 and 65535 recipients",
+            CryptoError::MessageTooLong => "message is too long",
+            CryptoError::UnsupportedVersion => "envelope version is not supported",
+            CryptoError::InvalidUtf8 => "decrypted message is not valid UTF-8",
+            CryptoError::PasswordRequired => "key is encrypted and needs a password",
+            CryptoError::FaultDetected => "fault detected in the private key operation",
+            CryptoError::EntropyRequired => "private key needs random blinding entropy before use",
+        };
+
+        f.write_str(message)
+    }
+}
+
+impl Error for CryptoError {}
+
+impl From<CryptoError> for JsValue {
+    fn from(error: CryptoError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("CryptoError");
+
+        let js_error = JsValue::from(js_error);
+        // Setting a property on a fresh Error object cannot fail.
+        let _ = js_sys::Reflect::set(
+            &js_error,
+            &JsValue::from_str("code"),
+            &JsValue::from_str(&error.code()),
+        );
+        js_error
+    }
+}
+
+#[cfg(test)]
+mod test_crypto_error {
+    use super::*;
+
+    #[test]
+    fn descriptive_messages() {
+        assert_eq!(
+            CryptoError::MalformedKey.to_string(),
+            "key could not be parsed"
+        );
+        assert_eq!(CryptoError::PrimeNotFound.code(), "PrimeNotFound");
+        assert_ne!(
+            CryptoError::MalformedKey.to_string(),
+            CryptoError::PrimeNotFound.to_string()
+        );
+    }
+}
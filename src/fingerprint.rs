@@ -0,0 +1,299 @@
+use error::CryptoError;
+use rsa::{Keypair, PublicKey};
+use sha2::{Digest, Sha256, Sha512};
+use wasm_bindgen::prelude::*;
+
+/// length of a fingerprint, a SHA-256 hash.
+pub const FINGERPRINT_LEN: usize = 32;
+
+/// how often the hash behind each half of a safety number is iterated, as in Signal.
+const SAFETY_NUMBER_ITERATIONS: usize = 5200;
+
+/// number of five digit groups in each half of a safety number.
+const SAFETY_NUMBER_GROUPS: usize = 6;
+
+/// the two-syllable words of the PGP word list, used for bytes at even positions.
+#[rustfmt::skip]
+static EVEN_WORDS: [&str; 256] = [
+    "aardvark", "absurd", "accrue", "acme", "adrift", "adult", "afflict", "ahead", "aimless", "Algol", "allow",
+    "alone", "ammo", "ancient", "apple", "artist", "assume", "Athens", "atlas", "Aztec", "baboon", "backfield",
+    "backward", "banjo", "beaming", "bedlamp", "beehive", "beeswax", "befriend", "Belfast", "berserk", "billiard",
+    "bison", "blackjack", "blockade", "blowtorch", "bluebird", "bombast", "bookshelf", "brackish", "breadline",
+    "breakup", "brickyard", "briefcase", "Burbank", "button", "buzzard", "cement", "chairlift", "chatter",
+    "checkup", "chisel", "choking", "chopper", "Christmas", "clamshell", "classic", "classroom", "cleanup",
+    "clockwork", "cobra", "commence", "concert", "cowbell", "crackdown", "cranky", "crowfoot", "crucial",
+    "crumpled", "crusade", "cubic", "dashboard", "deadbolt", "deckhand", "dogsled", "dragnet", "drainage",
+    "dreadful", "drifter", "dropper", "drumbeat", "drunken", "Dupont", "dwelling", "eating", "edict", "egghead",
+    "eightball", "endorse", "endow", "enlist", "erase", "escape", "exceed", "eyeglass", "eyetooth", "facial",
+    "fallout", "flagpole", "flatfoot", "flytrap", "fracture", "framework", "freedom", "frighten", "gazelle",
+    "Geiger", "glitter", "glucose", "goggles", "goldfish", "gremlin", "guidance", "hamlet", "highchair", "hockey",
+    "indoors", "indulge", "inverse", "involve", "island", "jawbone", "keyboard", "kickoff", "kiwi", "klaxon",
+    "locale", "lockup", "merit", "minnow", "miser", "Mohawk", "mural", "music", "necklace", "Neptune", "newborn",
+    "nightbird", "Oakland", "obtuse", "offload", "optic", "orca", "payday", "peachy", "pheasant", "physique",
+    "playhouse", "Pluto", "preclude", "prefer", "preshrunk", "printer", "prowler", "pupil", "puppy", "python",
+    "quadrant", "quiver", "quota", "ragtime", "ratchet", "rebirth", "reform", "regain", "reindeer", "rematch",
+    "repay", "retouch", "revenge", "reward", "rhythm", "ribcage", "ringbolt", "robust", "rocker", "ruffled",
+    "sailboat", "sawdust", "scallion", "scenic", "scorecard", "Scotland", "seabird", "select", "sentence", "shadow",
+    "shamrock", "showgirl", "skullcap", "skydive", "slingshot", "slowdown", "snapline", "snapshot", "snowcap",
+    "snowslide", "solo", "southward", "soybean", "spaniel", "spearhead", "spellbind", "spheroid", "spigot",
+    "spindle", "spyglass", "stagehand", "stagnate", "stairway", "standard", "stapler", "steamship", "sterling",
+    "stockman", "stopwatch", "stormy", "sugar", "surmount", "suspense", "sweatband", "swelter", "tactics", "talon",
+    "tapeworm", "tempest", "tiger", "tissue", "tonic", "topmost", "tracker", "transit", "trauma", "treadmill",
+    "Trojan", "trouble", "tumor", "tunnel", "tycoon", "uncut", "unearth", "unwind", "uproot", "upset", "upshot",
+    "vapor", "village", "virus", "Vulcan", "waffle", "wallet", "watchword", "wayside", "willow", "woodlark", "Zulu",
+];
+
+/// the three-syllable words of the PGP word list, used for bytes at odd positions.
+#[rustfmt::skip]
+static ODD_WORDS: [&str; 256] = [
+    "adroitness", "adviser", "aftermath", "aggregate", "alkali", "almighty", "amulet", "amusement", "antenna",
+    "applicant", "Apollo", "armistice", "article", "asteroid", "Atlantic", "atmosphere", "autopsy", "Babylon",
+    "backwater", "barbecue", "belowground", "bifocals", "bodyguard", "bookseller", "borderline", "bottomless",
+    "Bradbury", "bravado", "Brazilian", "breakaway", "Burlington", "businessman", "butterfat", "Camelot",
+    "candidate", "cannonball", "Capricorn", "caravan", "caretaker", "celebrate", "cellulose", "certify",
+    "chambermaid", "Cherokee", "Chicago", "clergyman", "coherence", "combustion", "commando", "company",
+    "component", "concurrent", "confidence", "conformist", "congregate", "consensus", "consulting", "corporate",
+    "corrosion", "councilman", "crossover", "crucifix", "cumbersome", "customer", "Dakota", "decadence", "December",
+    "decimal", "designing", "detector", "detergent", "determine", "dictator", "dinosaur", "direction", "disable",
+    "disbelief", "disruptive", "distortion", "document", "embezzle", "enchanting", "enrollment", "enterprise",
+    "equation", "equipment", "escapade", "Eskimo", "everyday", "examine", "existence", "exodus", "fascinate",
+    "filament", "finicky", "forever", "fortitude", "frequency", "gadgetry", "Galveston", "getaway", "glossary",
+    "gossamer", "graduate", "gravity", "guitarist", "hamburger", "Hamilton", "handiwork", "hazardous", "headwaters",
+    "hemisphere", "hesitate", "hideaway", "holiness", "hurricane", "hydraulic", "impartial", "impetus", "inception",
+    "indigo", "inertia", "infancy", "inferno", "informant", "insincere", "insurgent", "integrate", "intention",
+    "inventive", "Istanbul", "Jamaica", "Jupiter", "leprosy", "letterhead", "liberty", "maritime", "matchmaker",
+    "maverick", "Medusa", "megaton", "microscope", "microwave", "midsummer", "millionaire", "miracle", "misnomer",
+    "molasses", "molecule", "Montana", "monument", "mosquito", "narrative", "nebula", "newsletter", "Norwegian",
+    "October", "Ohio", "onlooker", "opulent", "Orlando", "outfielder", "Pacific", "pandemic", "Pandora",
+    "paperweight", "paragon", "paragraph", "paramount", "passenger", "pedigree", "Pegasus", "penetrate",
+    "perceptive", "performance", "pharmacy", "phonetic", "photograph", "pioneer", "pocketful", "politeness",
+    "positive", "potato", "processor", "provincial", "proximate", "puberty", "publisher", "pyramid", "quantity",
+    "racketeer", "rebellion", "recipe", "recover", "repellent", "replica", "reproduce", "resistor", "responsive",
+    "retraction", "retrieval", "retrospect", "revenue", "revival", "revolver", "sandalwood", "sardonic", "Saturday",
+    "savagery", "scavenger", "sensation", "sociable", "souvenir", "specialist", "speculate", "stethoscope",
+    "stupendous", "supportive", "surrender", "suspicious", "sympathy", "tambourine", "telephone", "therapist",
+    "tobacco", "tolerance", "tomorrow", "torpedo", "tradition", "travesty", "trombonist", "truncated", "typewriter",
+    "ultimate", "undaunted", "underfoot", "unicorn", "unify", "universe", "unravel", "upcoming", "vacancy",
+    "vagabond", "vertigo", "Virginia", "visitor", "vocalist", "voyager", "warranty", "Waterloo", "whimsical",
+    "Wichita", "Wilmington", "Wyoming", "yesteryear", "Yucatan",
+];
+
+impl PublicKey {
+    /// the fingerprint of this key: the SHA-256 hash of its SubjectPublicKeyInfo (see `to_spki_der`), the same
+    /// value as `openssl pkey -pubin -outform DER | sha256sum`. Two keys only share a fingerprint if they are equal.
+    pub fn fingerprint(&self) -> [u8; FINGERPRINT_LEN] {
+        let mut fingerprint = [0; FINGERPRINT_LEN];
+        fingerprint.copy_from_slice(&Sha256::digest(self.to_spki_der()));
+        fingerprint
+    }
+
+    /// the fingerprint as groups of four uppercase hex digits, e.g. "F868 B292 ...".
+    pub fn fingerprint_hex(&self) -> String {
+        to_hex_groups(&self.fingerprint())
+    }
+
+    /// the fingerprint as 32 words of the PGP word list, which are easier to read out loud than hex.
+    pub fn fingerprint_words(&self) -> String {
+        to_pgp_words(&self.fingerprint())
+    }
+}
+
+/// formats bytes as groups of four uppercase hex digits separated by spaces.
+pub fn to_hex_groups(bytes: &[u8]) -> String {
+    bytes
+        .chunks(2)
+        .map(|pair| {
+            pair.iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// formats bytes as words of the PGP word list separated by spaces. Bytes at even positions use two-syllable words
+/// and bytes at odd positions three-syllable ones, so that swapped or dropped words are noticed.
+///
+/// ## Reference
+/// See [the PGP word list](https://en.wikipedia.org/wiki/PGP_word_list).
+pub fn to_pgp_words(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if i % 2 == 0 {
+                EVEN_WORDS[b as usize]
+            } else {
+                ODD_WORDS[b as usize]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// one half of a safety number: 30 digits derived from a key and the identifier of its owner by iterating SHA-512.
+fn safety_digits(key: &PublicKey, id: &str) -> String {
+    let key = key.to_spki_der();
+
+    // The version (0) as two bytes, the key and the identifier.
+    let mut hash = vec![0, 0];
+    hash.extend_from_slice(&key);
+    hash.extend_from_slice(id.as_bytes());
+
+    for _ in 0..SAFETY_NUMBER_ITERATIONS {
+        let mut digest = Sha512::new();
+        digest.update(&hash);
+        digest.update(&key);
+        hash = digest.finalize().to_vec();
+    }
+
+    hash.chunks(5)
+        .take(SAFETY_NUMBER_GROUPS)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+            format!("{:05}", value % 100_000)
+        })
+        .collect()
+}
+
+/// the safety number of a conversation between two users, given each one's public key and identifier (e.g. their
+/// username). It is 60 digits in groups of five, the same on both sides, so the users can compare it in person or
+/// over a call to make sure that neither key was swapped.
+///
+/// ## Reference
+/// See [Signal's numeric fingerprints](https://signal.org/blog/safety-number-updates/); the halves are computed as
+/// in Signal's `NumericFingerprintGenerator`, over the SubjectPublicKeyInfo of the RSA keys.
+pub fn safety_number(key: &PublicKey, id: &str, other_key: &PublicKey, other_id: &str) -> String {
+    let mine = safety_digits(key, id);
+    let theirs = safety_digits(other_key, other_id);
+
+    // The lower half goes first, so both users see the same number.
+    let digits = if mine <= theirs {
+        mine + &theirs
+    } else {
+        theirs + &mine
+    };
+
+    digits
+        .as_bytes()
+        .chunks(5)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// given a public key in any format `PublicKey::parse` reads, returns its fingerprint as hex groups (see
+/// `PublicKey::fingerprint_hex`), to show next to a user's key.
+#[wasm_bindgen]
+pub fn fingerprint_hex(public_key: &str) -> Result<String, CryptoError> {
+    Ok(PublicKey::parse(public_key)?.fingerprint_hex())
+}
+
+/// given a public key in any format `PublicKey::parse` reads, returns its fingerprint as PGP words (see
+/// `PublicKey::fingerprint_words`).
+#[wasm_bindgen]
+pub fn fingerprint_words(public_key: &str) -> Result<String, CryptoError> {
+    Ok(PublicKey::parse(public_key)?.fingerprint_words())
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// the fingerprint of the public key as hex groups.
+    pub fn fingerprint_hex(&self) -> String {
+        self.public_key().fingerprint_hex()
+    }
+
+    /// the fingerprint of the public key as PGP words.
+    pub fn fingerprint_words(&self) -> String {
+        self.public_key().fingerprint_words()
+    }
+
+    /// the safety number of a conversation with another user (see `safety_number`), where my_id is the identifier
+    /// of the owner of this keypair. Fails if the other public key cannot be parsed.
+    pub fn safety_number(
+        &self,
+        my_id: &str,
+        their_public_key: &str,
+        their_id: &str,
+    ) -> Result<String, CryptoError> {
+        let theirs = PublicKey::parse(their_public_key)?;
+        Ok(safety_number(&self.public_key(), my_id, &theirs, their_id))
+    }
+}
+
+#[cfg(test)]
+mod test_fingerprint {
+    use super::*;
+    use crypto_math::string_to_number;
+    use rsa::test_keypair;
+
+    #[test]
+    fn matches_openssl() {
+        let k = test_keypair();
+
+        // `openssl rsa -pubout -outform DER | sha256sum` for the key from `test_keypair`.
+        assert_eq!(
+            k.fingerprint_hex(),
+            "F868 B292 6BF0 B7DC C786 C558 6804 35EB D8A5 DA4A 40DB D639 710A 86C3 4314 5911"
+        );
+        assert_eq!(
+            fingerprint_hex(&k.public_key_display_wasm()),
+            Ok(k.fingerprint_hex())
+        );
+        assert_eq!(
+            fingerprint_hex("not a key!"),
+            Err(CryptoError::MalformedKey)
+        );
+    }
+
+    #[test]
+    fn pgp_words() {
+        // The example from the PGP word list.
+        let bytes = [
+            0xE5, 0x82, 0x94, 0xF2, 0xE9, 0xA2, 0x27, 0x48, 0x6E, 0x8B, 0x06, 0x1B, 0x31, 0xCC,
+            0x52, 0x8F, 0xD7, 0xFA, 0x3F, 0x19,
+        ];
+        assert_eq!(
+            to_pgp_words(&bytes),
+            "topmost Istanbul Pluto vagabond treadmill Pacific brackish dictator goldfish Medusa \
+             afflict bravado chatter revolver Dupont midsummer stopwatch whimsical cowbell bottomless"
+        );
+        assert_eq!(
+            to_pgp_words(&[0, 0, 0xFF, 0xFF]),
+            "aardvark adroitness Zulu Yucatan"
+        );
+
+        let words = test_keypair().fingerprint_words();
+        assert_eq!(words.split(' ').count(), FINGERPRINT_LEN);
+        assert!(words.starts_with("Vulcan gravity sawdust misnomer "));
+    }
+
+    #[test]
+    fn safety_numbers() {
+        let alice = test_keypair();
+        let bob = PublicKey::new(string_to_number("3"), string_to_number("1022117"));
+
+        let number = alice
+            .safety_number("alice", &bob.to_spki_pem(), "bob")
+            .unwrap();
+        assert_eq!(
+            number,
+            "58094 30195 37627 93734 39316 44267 68700 41138 04271 24867 68454 68746"
+        );
+
+        // Both sides see the same number, which changes with either key or identifier.
+        assert_eq!(
+            safety_number(&bob, "bob", &alice.public_key(), "alice"),
+            number
+        );
+        assert_ne!(
+            safety_number(&alice.public_key(), "alice", &bob, "mallory"),
+            number
+        );
+        let other = PublicKey::new(string_to_number("5"), string_to_number("1022117"));
+        assert_ne!(
+            safety_number(&alice.public_key(), "alice", &other, "bob"),
+            number
+        );
+    }
+}
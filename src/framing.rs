@@ -0,0 +1,291 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use error::CryptoError;
+use rsa::{Keypair, PublicKey};
+use wasm_bindgen::prelude::*;
+
+/// the first byte of every frame. Bump it whenever the header layout changes.
+pub const FRAME_VERSION: u8 = 1;
+
+/// length of a key ID, the truncated SHA-256 fingerprint of the recipient's public key.
+pub const KEY_ID_LEN: usize = 8;
+
+/// length of the frame header: version, algorithm and key ID.
+const HEADER_LEN: usize = 2 + KEY_ID_LEN;
+
+/// the encryption schemes a framed payload can use.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// a hybrid RSA-OAEP and AES-256-GCM envelope from `PublicKey::seal_bytes`.
+    Sealed = 1,
+    /// fixed-width RSAES-OAEP blocks from `PublicKey::encrypt_bytes`.
+    Oaep = 2,
+}
+
+impl Algorithm {
+    /// the algorithm with the given identifier, if it is known.
+    fn from_id(id: u8) -> Option<Algorithm> {
+        match id {
+            1 => Some(Algorithm::Sealed),
+            2 => Some(Algorithm::Oaep),
+            _ => None,
+        }
+    }
+}
+
+impl PublicKey {
+    /// the ID of this key as used in frames: the first `KEY_ID_LEN` bytes of its `fingerprint`. It is short
+    /// enough to put in every message, and, unlike the modulus, does not tell observers which key it belongs to
+    /// unless they already know the key.
+    pub fn key_id(&self) -> [u8; KEY_ID_LEN] {
+        let mut key_id = [0; KEY_ID_LEN];
+        key_id.copy_from_slice(&self.fingerprint()[..KEY_ID_LEN]);
+        key_id
+    }
+}
+
+/// a message together with the header that says who it is for and how it was encrypted. A frame is laid out as
+///
+/// ```text
+/// version (1 byte) || algorithm (1 byte) || recipient key ID (8 bytes) || payload
+/// ```
+///
+/// The header is only a routing hint: it is not authenticated, so changing it can at most make a message
+/// undeliverable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// how the payload was encrypted.
+    pub algorithm: Algorithm,
+    /// the `PublicKey::key_id` of the recipient.
+    pub key_id: [u8; KEY_ID_LEN],
+    /// the ciphertext.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// encrypts message m for the recipient with the chosen algorithm and frames the result. The seed drives the
+    /// randomness of the algorithm.
+    pub fn encrypt(
+        m: &[u8],
+        recipient: &PublicKey,
+        seed: &[u8],
+        algorithm: Algorithm,
+    ) -> Result<Frame, CryptoError> {
+        let payload = match algorithm {
+            Algorithm::Sealed => recipient.seal_bytes(m, seed)?,
+            Algorithm::Oaep => recipient.encrypt_bytes(m, seed)?,
+        };
+
+        Ok(Frame {
+            algorithm,
+            key_id: recipient.key_id(),
+            payload,
+        })
+    }
+
+    /// the bytes of this frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.push(FRAME_VERSION);
+        bytes.push(self.algorithm as u8);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// parses a frame. Fails with `CryptoError::UnsupportedVersion` for frames of another version or with an
+    /// unknown algorithm, and with `CryptoError::MalformedCiphertext` if the header is truncated.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Frame, CryptoError> {
+        let (algorithm, key_id) = parse_header(bytes)?;
+        Ok(Frame {
+            algorithm,
+            key_id,
+            payload: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// the frame as unpadded base64url text, to be sent over the chat socket.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.to_bytes())
+    }
+
+    /// parses a frame from unpadded base64url text. Fails like `from_bytes`, or with
+    /// `CryptoError::MalformedCiphertext` if the text is not base64url.
+    pub fn decode(frame: &str) -> Result<Frame, CryptoError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(frame.trim())
+            .map_err(|_| CryptoError::MalformedCiphertext)?;
+        Frame::from_bytes(&bytes)
+    }
+}
+
+/// reads the algorithm and key ID from the header at the start of bytes.
+fn parse_header(bytes: &[u8]) -> Result<(Algorithm, [u8; KEY_ID_LEN]), CryptoError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(CryptoError::MalformedCiphertext);
+    }
+    if bytes[0] != FRAME_VERSION {
+        return Err(CryptoError::UnsupportedVersion);
+    }
+
+    let algorithm = Algorithm::from_id(bytes[1]).ok_or(CryptoError::UnsupportedVersion)?;
+    let mut key_id = [0; KEY_ID_LEN];
+    key_id.copy_from_slice(&bytes[2..HEADER_LEN]);
+    Ok((algorithm, key_id))
+}
+
+/// given a public key in any format `PublicKey::parse` reads, encrypts message m with the chosen algorithm and
+/// returns it as a base64url frame (see `Frame`), which replaces the `[public key]:` prefix that used to be put in
+/// front of every message.
+#[wasm_bindgen]
+pub fn encrypt_framed(
+    m: &str,
+    recipient_public_key: &str,
+    seed: &[u8],
+    algorithm: Algorithm,
+) -> Result<String, CryptoError> {
+    let recipient = PublicKey::parse(recipient_public_key)?;
+    Ok(Frame::encrypt(m.as_bytes(), &recipient, seed, algorithm)?.encode())
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// checks whether a base64url frame is addressed to this keypair, without decoding or decrypting the payload.
+    /// Returns false for anything that is not a frame.
+    pub fn is_addressed_to_me(&self, frame: &str) -> bool {
+        // 16 base64 characters hold 12 bytes, which covers the header.
+        let header = match frame.trim().get(..16) {
+            Some(header) => header,
+            None => return false,
+        };
+
+        match URL_SAFE_NO_PAD.decode(header) {
+            Ok(bytes) => parse_header(&bytes)
+                .map(|(_, key_id)| key_id == self.public_key().key_id())
+                .unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// decrypts a base64url frame produced by `encrypt_framed` and returns the message. Fails like
+    /// `Frame::decode`, with `CryptoError::WrongRecipient` if the frame is for another key, and like the
+    /// decryption of its algorithm otherwise.
+    pub fn decrypt_framed(&self, frame: &str) -> Result<String, CryptoError> {
+        let frame = Frame::decode(frame)?;
+        if frame.key_id != self.public_key().key_id() {
+            return Err(CryptoError::WrongRecipient);
+        }
+
+        let message = match frame.algorithm {
+            Algorithm::Sealed => self.open_bytes(&frame.payload)?,
+            Algorithm::Oaep => self.decrypt_bytes(&frame.payload)?,
+        };
+        String::from_utf8(message).map_err(|_| CryptoError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod test_framing {
+    use super::*;
+    use crypto_math::test_seed;
+    use rsa::{test_keypair, KeypairBuilder, MULTILINGUAL_MESSAGES};
+
+    #[test]
+    fn round_trip() {
+        let k = test_keypair();
+        let public_key = k.public_key_pem();
+
+        for &algorithm in &[Algorithm::Sealed, Algorithm::Oaep] {
+            for plaintext in MULTILINGUAL_MESSAGES.iter().chain(&["", "Hello World!"]) {
+                let frame = encrypt_framed(plaintext, &public_key, test_seed(), algorithm).unwrap();
+                assert!(k.is_addressed_to_me(&frame));
+                assert_eq!(k.decrypt_framed(&frame), Ok(plaintext.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn header_layout() {
+        let key = test_keypair().public_key();
+        let frame = Frame::encrypt(b"hi", &key, test_seed(), Algorithm::Oaep).unwrap();
+
+        let bytes = frame.to_bytes();
+        assert_eq!(bytes[0], FRAME_VERSION);
+        assert_eq!(bytes[1], 2);
+        assert_eq!(&bytes[2..HEADER_LEN], &key.key_id());
+        assert_eq!(bytes.len(), HEADER_LEN + 128);
+        assert_eq!(Frame::from_bytes(&bytes), Ok(frame.clone()));
+        assert_eq!(Frame::decode(&frame.encode()), Ok(frame));
+    }
+
+    #[test]
+    fn key_id_is_a_truncated_fingerprint() {
+        let key = test_keypair().public_key();
+        assert_eq!(&key.key_id()[..], &key.fingerprint()[..KEY_ID_LEN]);
+        assert_eq!(
+            key.key_id(),
+            [0xF8, 0x68, 0xB2, 0x92, 0x6B, 0xF0, 0xB7, 0xDC]
+        );
+
+        // The modulus is no longer part of the message.
+        let frame = Frame::encrypt(b"hi", &key, test_seed(), Algorithm::Sealed).unwrap();
+        let n = key.n().to_bytes_be().1;
+        assert!(!frame.to_bytes().windows(n.len()).any(|w| w == &n[..]));
+    }
+
+    #[test]
+    fn other_recipient() {
+        let k = test_keypair();
+        let other = KeypairBuilder::new()
+            .modulus_bits(1024)
+            .build(&[5; 32], &[6; 32])
+            .unwrap();
+        let frame = encrypt_framed(
+            "Hello World!",
+            &other.public_key_display_wasm(),
+            test_seed(),
+            Algorithm::Sealed,
+        )
+        .unwrap();
+
+        assert!(!k.is_addressed_to_me(&frame));
+        assert!(other.is_addressed_to_me(&frame));
+        assert_eq!(k.decrypt_framed(&frame), Err(CryptoError::WrongRecipient));
+    }
+
+    #[test]
+    fn rejects_bad_frames() {
+        let k = test_keypair();
+        let frame = Frame::encrypt(b"hi", &k.public_key(), test_seed(), Algorithm::Sealed).unwrap();
+        let bytes = frame.to_bytes();
+
+        assert_eq!(
+            Frame::from_bytes(&bytes[..HEADER_LEN - 1]),
+            Err(CryptoError::MalformedCiphertext)
+        );
+        assert_eq!(
+            Frame::decode("not base64!"),
+            Err(CryptoError::MalformedCiphertext)
+        );
+
+        let mut other_version = bytes.clone();
+        other_version[0] = FRAME_VERSION + 1;
+        assert_eq!(
+            Frame::from_bytes(&other_version),
+            Err(CryptoError::UnsupportedVersion)
+        );
+
+        let mut unknown_algorithm = bytes.clone();
+        unknown_algorithm[1] = 0;
+        assert_eq!(
+            Frame::from_bytes(&unknown_algorithm),
+            Err(CryptoError::UnsupportedVersion)
+        );
+
+        for text in &["", "short", "not base64 at all!", "[key]:\n12,34"] {
+            assert!(!k.is_addressed_to_me(text));
+        }
+        assert!(!k.is_addressed_to_me(&URL_SAFE_NO_PAD.encode(&other_version)));
+    }
+}
@@ -0,0 +1,491 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crypto_math::from_slice;
+use error::CryptoError;
+use padding::oaep_max_message_len;
+use rand::{Rng, SeedableRng, StdRng};
+use rsa::{Keypair, PublicKey};
+use wasm_bindgen::prelude::*;
+
+/// the first byte of every single recipient envelope. Bump it whenever the layout changes.
+pub const VERSION: u8 = 1;
+
+/// the first byte of every multi-recipient envelope.
+pub const MULTI_VERSION: u8 = 2;
+
+/// the most recipients a multi-recipient envelope can hold, as the count is stored in two bytes.
+pub const MAX_RECIPIENTS: usize = 0xffff;
+
+/// length of the random AES-256 content key.
+const CONTENT_KEY_LEN: usize = 32;
+
+/// length of the AES-GCM nonce.
+const NONCE_LEN: usize = 12;
+
+/// length of the AES-GCM authentication tag.
+const TAG_LEN: usize = 16;
+
+impl PublicKey {
+    /// encrypts message m for this key with a hybrid scheme: a random AES-256 content key is wrapped with
+    /// RSAES-OAEP (see `encrypt_bytes`) and m is encrypted under it with AES-256-GCM. However long m is, this costs
+    /// a single RSA operation. The envelope is laid out as
+    ///
+    /// ```text
+    /// version (1 byte) || wrapped content key (size() bytes) || nonce (12 bytes) || AES-GCM ciphertext and tag
+    /// ```
+    ///
+    /// and the version and wrapped key are authenticated as associated data, so that neither can be swapped. The
+    /// seed drives the content key, the nonce and the OAEP padding. Fails if the content key does not fit in a
+    /// single OAEP block, that is for keys under 777 bits.
+    ///
+    /// ## Reference
+    /// See [NIST SP 800-38D](https://doi.org/10.6028/NIST.SP.800-38D) for GCM.
+    pub fn seal_bytes(&self, m: &[u8], seed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+        let mut content_key = [0; CONTENT_KEY_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        let mut oaep_seed = [0; 32];
+        rng.fill(&mut content_key);
+        rng.fill(&mut nonce);
+        rng.fill(&mut oaep_seed);
+
+        let mut header = vec![VERSION];
+        header.extend(self.wrap_content_key(&content_key, &oaep_seed)?);
+
+        seal_body(header, &content_key, &nonce, m)
+    }
+
+    /// wraps the content key with RSAES-OAEP into a single block of `size()` bytes, the only size `open_bytes`
+    /// reads. Fails with `CryptoError::KeyTooSmall` if it would take more than one block.
+    fn wrap_content_key(
+        &self,
+        content_key: &[u8; CONTENT_KEY_LEN],
+        oaep_seed: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        if oaep_max_message_len(self.size()) < CONTENT_KEY_LEN {
+            return Err(CryptoError::KeyTooSmall);
+        }
+
+        self.encrypt_bytes(content_key, oaep_seed)
+    }
+}
+
+/// encrypts message m for several recipients at once. The body is encrypted a single time as in
+/// `PublicKey::seal_bytes`, and the content key is wrapped separately for each recipient. The envelope is laid out as
+///
+/// ```text
+/// version (1 byte) || slot count (2 bytes) || slots || nonce (12 bytes) || AES-GCM ciphertext and tag
+/// ```
+///
+/// where every slot is a two byte length followed by the wrapped content key, and everything before the nonce is
+/// authenticated as associated data. The slots carry no key IDs and are shuffled, so observers only learn how many
+/// recipients there are and the sizes of their keys; each recipient finds its slot by trying to unwrap them. The
+/// seed drives the content key, the nonce, the OAEP padding and the order of the slots. Fails if there are no
+/// recipients or more than `MAX_RECIPIENTS`, or if a key is too small to wrap the content key in a single OAEP
+/// block (see `PublicKey::seal_bytes`).
+pub fn seal_to_many_bytes(
+    m: &[u8],
+    recipients: &[PublicKey],
+    seed: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+        return Err(CryptoError::InvalidRecipients);
+    }
+
+    let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+    let mut content_key = [0; CONTENT_KEY_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    rng.fill(&mut content_key);
+    rng.fill(&mut nonce);
+
+    let mut slots = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        if recipient.size() > 0xffff {
+            return Err(CryptoError::UnsupportedKey);
+        }
+
+        let mut oaep_seed = [0; 32];
+        rng.fill(&mut oaep_seed);
+        slots.push(recipient.wrap_content_key(&content_key, &oaep_seed)?);
+    }
+    rng.shuffle(&mut slots);
+
+    let mut header = vec![MULTI_VERSION];
+    header.extend_from_slice(&(slots.len() as u16).to_be_bytes());
+    for slot in &slots {
+        header.extend_from_slice(&(slot.len() as u16).to_be_bytes());
+        header.extend_from_slice(slot);
+    }
+
+    seal_body(header, &content_key, &nonce, m)
+}
+
+/// encrypts message m under the content key with AES-256-GCM and appends the nonce and the ciphertext to the
+/// header, which is authenticated as associated data.
+///
+/// ## Reference
+/// See [NIST SP 800-38D](https://doi.org/10.6028/NIST.SP.800-38D).
+fn seal_body(
+    mut header: Vec<u8>,
+    content_key: &[u8; CONTENT_KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    m: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    // AES-GCM only refuses messages of more than 64 GiB.
+    let body = Aes256Gcm::new(content_key.into())
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: m,
+                aad: &header,
+            },
+        )
+        .map_err(|_| CryptoError::MessageTooLong)?;
+
+    header.extend_from_slice(nonce);
+    header.extend(body);
+    Ok(header)
+}
+
+/// decrypts the nonce and ciphertext that follow the header with the content key, checking the header as
+/// associated data.
+fn open_body(header: &[u8], content_key: &[u8], rest: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if rest.len() < NONCE_LEN + TAG_LEN {
+        return Err(CryptoError::MalformedCiphertext);
+    }
+
+    let (nonce, body) = rest.split_at(NONCE_LEN);
+    let cipher =
+        Aes256Gcm::new_from_slice(content_key).map_err(|_| CryptoError::DecryptionFailed)?;
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: body,
+                aad: header,
+            },
+        )
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+/// the parts of a multi-recipient envelope.
+struct MultiEnvelope<'a> {
+    /// everything before the nonce, authenticated as associated data
+    header: &'a [u8],
+    /// the content key wrapped for each recipient
+    slots: Vec<&'a [u8]>,
+    /// the nonce and the AES-GCM ciphertext
+    rest: &'a [u8],
+}
+
+/// splits a multi-recipient envelope into its parts. Returns None if the envelope is truncated.
+fn split_slots(envelope: &[u8]) -> Option<MultiEnvelope<'_>> {
+    let read_u16 = |bytes: &[u8], at: usize| -> Option<usize> {
+        let pair = bytes.get(at..at + 2)?;
+        Some(u16::from_be_bytes([pair[0], pair[1]]) as usize)
+    };
+
+    let count = read_u16(envelope, 1)?;
+    let mut offset = 3;
+    let mut slots = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let len = read_u16(envelope, offset)?;
+        slots.push(envelope.get(offset + 2..offset + 2 + len)?);
+        offset += 2 + len;
+    }
+
+    Some(MultiEnvelope {
+        header: &envelope[..offset],
+        slots,
+        rest: &envelope[offset..],
+    })
+}
+
+/// given a public key in any format `PublicKey::parse` reads, seals message m with `PublicKey::seal_bytes` and
+/// encodes the envelope as unpadded base64url, so that it can be sent over the chat socket as text.
+#[wasm_bindgen]
+pub fn seal(m: &str, recipient_public_key: &str, seed: &[u8]) -> Result<String, CryptoError> {
+    let envelope = PublicKey::parse(recipient_public_key)?.seal_bytes(m.as_bytes(), seed)?;
+    Ok(URL_SAFE_NO_PAD.encode(envelope))
+}
+
+/// given the public keys of a group chat, in any formats `PublicKey::parse` reads, seals message m for all of them
+/// with `seal_to_many_bytes` and encodes the envelope as unpadded base64url. In JavaScript the keys are passed as
+/// an array of strings.
+#[wasm_bindgen]
+pub fn seal_to_many(
+    m: &str,
+    recipient_public_keys: Vec<String>,
+    seed: &[u8],
+) -> Result<String, CryptoError> {
+    let recipients = recipient_public_keys
+        .iter()
+        .map(|key| PublicKey::parse(key))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let envelope = seal_to_many_bytes(m.as_bytes(), &recipients, seed)?;
+    Ok(URL_SAFE_NO_PAD.encode(envelope))
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// opens an envelope produced by `PublicKey::seal_bytes` or `seal_to_many_bytes` for this keypair. In a
+    /// multi-recipient envelope every slot that fits the key size is tried. Fails with
+    /// `CryptoError::UnsupportedVersion` for envelopes of another version, with `CryptoError::MalformedCiphertext`
+    /// if the envelope is truncated, and with `CryptoError::DecryptionFailed` if it was not sealed for this key or
+    /// was tampered with.
+    pub fn open_bytes(&self, envelope: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let k = self.public_key().size();
+
+        match envelope.first() {
+            Some(&VERSION) => {
+                if envelope.len() < 1 + k {
+                    return Err(CryptoError::MalformedCiphertext);
+                }
+
+                let (header, rest) = envelope.split_at(1 + k);
+                let content_key = self.decrypt_bytes(&header[1..])?;
+                open_body(header, &content_key, rest)
+            }
+            Some(&MULTI_VERSION) => {
+                let parts = split_slots(envelope).ok_or(CryptoError::MalformedCiphertext)?;
+                if parts.rest.len() < NONCE_LEN + TAG_LEN {
+                    return Err(CryptoError::MalformedCiphertext);
+                }
+
+                parts
+                    .slots
+                    .iter()
+                    .filter(|slot| slot.len() == k)
+                    .filter_map(|slot| self.decrypt_bytes(slot).ok())
+                    .filter_map(|key| open_body(parts.header, &key, parts.rest).ok())
+                    .next()
+                    .ok_or(CryptoError::DecryptionFailed)
+            }
+            Some(_) => Err(CryptoError::UnsupportedVersion),
+            None => Err(CryptoError::MalformedCiphertext),
+        }
+    }
+
+    /// opens a base64url envelope produced by `seal` and returns the message. Fails like `open_bytes`, with
+    /// `CryptoError::MalformedCiphertext` if the envelope is not base64url, and with `CryptoError::InvalidUtf8` if
+    /// the message is not text.
+    pub fn open(&self, envelope: &str) -> Result<String, CryptoError> {
+        let envelope = URL_SAFE_NO_PAD
+            .decode(envelope.trim())
+            .map_err(|_| CryptoError::MalformedCiphertext)?;
+        let message = self.open_bytes(&envelope)?;
+        String::from_utf8(message).map_err(|_| CryptoError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod test_hybrid {
+    use super::*;
+    use crypto_math::test_seed;
+    use num::bigint::BigInt;
+    use num_traits::One;
+    use rsa::{test_keypair, KeypairBuilder, MULTILINGUAL_MESSAGES};
+
+    #[test]
+    fn round_trip() {
+        let k = test_keypair();
+        let public_key = k.public_key_pem();
+
+        for plaintext in MULTILINGUAL_MESSAGES.iter().chain(&["", "Hello World!"]) {
+            let envelope = seal(plaintext, &public_key, test_seed()).unwrap();
+            assert_eq!(k.open(&envelope), Ok(plaintext.to_string()));
+        }
+    }
+
+    #[test]
+    fn long_message_needs_one_rsa_block() {
+        let k = test_keypair();
+        let m = vec![b'x'; 10_000];
+
+        let envelope = k.public_key().seal_bytes(&m, test_seed()).unwrap();
+        assert_eq!(envelope[0], VERSION);
+        assert_eq!(envelope.len(), 1 + 128 + NONCE_LEN + m.len() + TAG_LEN);
+        assert_eq!(k.open_bytes(&envelope), Ok(m));
+    }
+
+    #[test]
+    fn randomized_by_seed() {
+        let key = test_keypair().public_key();
+        let one = key.seal_bytes(b"hi", test_seed()).unwrap();
+        let two = key.seal_bytes(b"hi", &[2; 32]).unwrap();
+        assert_ne!(one, two);
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let k = test_keypair();
+        let envelope = k
+            .public_key()
+            .seal_bytes(b"Hello World!", test_seed())
+            .unwrap();
+
+        // The wrapped key, the nonce and the body are all covered.
+        for &i in &[1, 100, 1 + 128, envelope.len() - 1] {
+            let mut tampered = envelope.clone();
+            tampered[i] ^= 1;
+            assert_eq!(k.open_bytes(&tampered), Err(CryptoError::DecryptionFailed));
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions_and_short_input() {
+        let k = test_keypair();
+        let mut envelope = k.public_key().seal_bytes(b"hi", test_seed()).unwrap();
+
+        assert_eq!(
+            k.open_bytes(&envelope[..envelope.len() - 1]),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            k.open_bytes(&envelope[..1 + 128 + NONCE_LEN]),
+            Err(CryptoError::MalformedCiphertext)
+        );
+        assert_eq!(k.open_bytes(&[]), Err(CryptoError::MalformedCiphertext));
+        assert_eq!(k.open("not base64!"), Err(CryptoError::MalformedCiphertext));
+
+        envelope[0] = MULTI_VERSION + 1;
+        assert_eq!(
+            k.open_bytes(&envelope),
+            Err(CryptoError::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    fn content_key_must_fit_one_block() {
+        // A 776-bit key holds OAEP messages of up to 31 bytes, one short of the content key.
+        let modulus = |bits: usize| (BigInt::one() << (bits - 1)) + 1;
+        let too_small = PublicKey::new(BigInt::from(65537), modulus(776));
+        assert_eq!(
+            too_small.seal_bytes(b"hi", test_seed()),
+            Err(CryptoError::KeyTooSmall)
+        );
+
+        let just_enough = PublicKey::new(BigInt::from(65537), modulus(777));
+        let envelope = just_enough.seal_bytes(b"hi", test_seed()).unwrap();
+        assert_eq!(envelope.len(), 1 + 98 + NONCE_LEN + 2 + TAG_LEN);
+    }
+
+    /// a 1024-bit keypair other than `test_keypair`.
+    fn other_keypair(seed: u8) -> Keypair {
+        KeypairBuilder::new()
+            .modulus_bits(1024)
+            .build(&[seed; 32], &[seed + 1; 32])
+            .unwrap()
+    }
+
+    #[test]
+    fn group_message() {
+        let alice = test_keypair();
+        let bob = other_keypair(5);
+        let carol = other_keypair(7);
+        let recipients = vec![alice.public_key_pem(), bob.public_key_openssh("bob")];
+
+        let envelope = seal_to_many("Hi both 👋", recipients, test_seed()).unwrap();
+        assert_eq!(alice.open(&envelope), Ok("Hi both 👋".to_string()));
+        assert_eq!(bob.open(&envelope), Ok("Hi both 👋".to_string()));
+        assert_eq!(carol.open(&envelope), Err(CryptoError::DecryptionFailed));
+
+        // The body is encrypted once, next to one slot per recipient.
+        let m = vec![b'x'; 1000];
+        let keys = [alice.public_key(), bob.public_key(), carol.public_key()];
+        let envelope = seal_to_many_bytes(&m, &keys, test_seed()).unwrap();
+        assert_eq!(envelope[0], MULTI_VERSION);
+        assert_eq!(
+            envelope.len(),
+            3 + 3 * (2 + 128) + NONCE_LEN + m.len() + TAG_LEN
+        );
+        assert_eq!(carol.open_bytes(&envelope), Ok(m));
+    }
+
+    #[test]
+    fn hides_recipients() {
+        let alice = test_keypair();
+        let bob = other_keypair(5);
+        let keys = [alice.public_key(), bob.public_key()];
+        let envelope = seal_to_many_bytes(b"hi", &keys, test_seed()).unwrap();
+
+        // No slot contains a recipient's modulus, and the order of the slots depends on the seed.
+        for key in &keys {
+            let n = key.n().to_bytes_be().1;
+            assert!(!envelope.windows(n.len()).any(|window| window == &n[..]));
+        }
+
+        let alice_slot = split_slots(&envelope)
+            .unwrap()
+            .slots
+            .iter()
+            .position(|slot| alice.decrypt_bytes(slot).is_ok())
+            .unwrap();
+        let orders: Vec<usize> = (1..8u8)
+            .map(|seed| {
+                let envelope = seal_to_many_bytes(b"hi", &keys, &[seed; 32]).unwrap();
+                split_slots(&envelope)
+                    .unwrap()
+                    .slots
+                    .iter()
+                    .position(|slot| alice.decrypt_bytes(slot).is_ok())
+                    .unwrap()
+            })
+            .collect();
+        assert!(orders.iter().any(|&position| position != alice_slot));
+    }
+
+    #[test]
+    fn rejects_bad_group_envelopes() {
+        let k = test_keypair();
+        let keys = [k.public_key()];
+
+        assert_eq!(
+            seal_to_many_bytes(b"hi", &[], test_seed()),
+            Err(CryptoError::InvalidRecipients)
+        );
+
+        let envelope = seal_to_many_bytes(b"hi", &keys, test_seed()).unwrap();
+        assert_eq!(k.open_bytes(&envelope), Ok(b"hi".to_vec()));
+
+        // Cut inside the slot, and claim a second slot that is not there.
+        assert_eq!(
+            k.open_bytes(&envelope[..100]),
+            Err(CryptoError::MalformedCiphertext)
+        );
+        let mut extra_slot = envelope.clone();
+        extra_slot[2] = 2;
+        assert_eq!(
+            k.open_bytes(&extra_slot),
+            Err(CryptoError::MalformedCiphertext)
+        );
+
+        // Every recipient must be able to find its slot, which holds exactly one block.
+        let too_small = PublicKey::new(BigInt::from(65537), (BigInt::one() << 767) + 1);
+        assert_eq!(
+            seal_to_many_bytes(b"hi", &[k.public_key(), too_small], test_seed()),
+            Err(CryptoError::KeyTooSmall)
+        );
+
+        // The slots are authenticated together with the body.
+        let mut tampered = envelope.clone();
+        tampered[4] ^= 1;
+        assert_eq!(k.open_bytes(&tampered), Err(CryptoError::DecryptionFailed));
+    }
+
+    #[test]
+    fn wrong_recipient() {
+        let k = test_keypair();
+        let other = Keypair::new(&[3; 32], &[4; 32]).unwrap();
+
+        let envelope = seal("Hello World!", &k.public_key_display_wasm(), test_seed()).unwrap();
+        assert!(other.open(&envelope).is_err());
+    }
+}
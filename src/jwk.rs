@@ -0,0 +1,235 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crypto_math::bytes_to_number;
+use error::CryptoError;
+use num::bigint::BigInt;
+use rsa::{Blinding, Keypair, PublicKey};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+/// encodes a non-negative integer as an unpadded base64url string of its big-endian bytes.
+///
+/// ## Reference
+/// See [RFC 7518, section 6.3](https://tools.ietf.org/html/rfc7518#section-6.3).
+fn encode_member(num: &BigInt) -> String {
+    URL_SAFE_NO_PAD.encode(num.to_bytes_be().1)
+}
+
+/// reads a member holding a base64url encoded integer.
+fn decode_member(jwk: &Map<String, Value>, name: &str) -> Result<BigInt, CryptoError> {
+    let encoded = jwk
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or(CryptoError::MalformedKey)?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| CryptoError::MalformedKey)?;
+    Ok(bytes_to_number(&bytes))
+}
+
+/// parses a JWK and checks that it holds an RSA key.
+fn parse_rsa_jwk(jwk: &str) -> Result<Map<String, Value>, CryptoError> {
+    let jwk = match serde_json::from_str(jwk) {
+        Ok(Value::Object(jwk)) => jwk,
+        _ => return Err(CryptoError::MalformedKey),
+    };
+
+    match jwk.get("kty").and_then(Value::as_str) {
+        Some("RSA") => Ok(jwk),
+        Some(_) => Err(CryptoError::UnsupportedKey),
+        None => Err(CryptoError::MalformedKey),
+    }
+}
+
+impl PublicKey {
+    /// the RFC 7638 thumbprint of this key: the base64url encoded SHA-256 hash of its required JWK members in
+    /// lexicographic order. It is used as the `kid` of exported JWKs.
+    ///
+    /// ## Reference
+    /// See [RFC 7638, section 3](https://tools.ietf.org/html/rfc7638#section-3).
+    pub fn thumbprint(&self) -> String {
+        let canonical = format!(
+            r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+            encode_member(self.e()),
+            encode_member(self.n())
+        );
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+
+    /// the members of this key as a JWK object.
+    fn jwk_members(&self) -> Map<String, Value> {
+        let mut jwk = Map::new();
+        jwk.insert("kty".to_string(), Value::from("RSA"));
+        jwk.insert("kid".to_string(), Value::from(self.thumbprint()));
+        jwk.insert("n".to_string(), Value::from(encode_member(self.n())));
+        jwk.insert("e".to_string(), Value::from(encode_member(self.e())));
+        jwk
+    }
+
+    /// encodes this key as a JSON Web Key, with its thumbprint as `kid`.
+    ///
+    /// ## Reference
+    /// See [RFC 7517](https://tools.ietf.org/html/rfc7517) and
+    /// [RFC 7518, section 6.3.1](https://tools.ietf.org/html/rfc7518#section-6.3.1).
+    pub fn to_jwk(&self) -> String {
+        Value::Object(self.jwk_members()).to_string()
+    }
+
+    /// decodes an RSA JSON Web Key. Private members, if present, are ignored. Fails if the input is not JSON,
+    /// not an RSA key, lacks n or e, or does not pass `PublicKey::validate`.
+    pub fn from_jwk(jwk: &str) -> Result<PublicKey, CryptoError> {
+        let jwk = parse_rsa_jwk(jwk)?;
+        let key = PublicKey::new(decode_member(&jwk, "e")?, decode_member(&jwk, "n")?);
+
+        key.validate()?;
+        Ok(key)
+    }
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// outputs the public key as a JSON Web Key, which WebCrypto can import with `crypto.subtle.importKey("jwk", ...)`.
+    pub fn public_key_jwk(&self) -> String {
+        self.public_key().to_jwk()
+    }
+
+    /// exports the whole keypair as a private JSON Web Key. The `kid` is the thumbprint of the public key, so it
+    /// matches the one from `public_key_jwk`. Keep the output secret.
+    ///
+    /// ## Reference
+    /// See [RFC 7518, section 6.3.2](https://tools.ietf.org/html/rfc7518#section-6.3.2).
+    pub fn to_jwk(&self) -> String {
+        let mut jwk = self.public_key().jwk_members();
+        let private = [
+            ("d", &self.d),
+            ("p", &self.p),
+            ("q", &self.q),
+            ("dp", &self.dp),
+            ("dq", &self.dq),
+            ("qi", &self.qinv),
+        ];
+
+        for (name, value) in private.iter() {
+            jwk.insert(name.to_string(), Value::from(encode_member(value)));
+        }
+
+        Value::Object(jwk).to_string()
+    }
+
+    /// imports a keypair from a private JSON Web Key, e.g. one exported from WebCrypto, mixing the seed of at least
+    /// 32 bytes into its blinding generator. All of n, e, d, p, q, dp, dq and qi are required, keys with more than
+    /// two primes are not supported. Fails otherwise, or if the key does not pass `Keypair::validate`.
+    pub fn from_jwk(jwk: &str, seed: &[u8]) -> Result<Keypair, CryptoError> {
+        let jwk = parse_rsa_jwk(jwk)?;
+        if jwk.contains_key("oth") {
+            return Err(CryptoError::UnsupportedKey);
+        }
+
+        let keypair = Keypair {
+            n: decode_member(&jwk, "n")?,
+            e: decode_member(&jwk, "e")?,
+            d: decode_member(&jwk, "d")?,
+            p: decode_member(&jwk, "p")?,
+            q: decode_member(&jwk, "q")?,
+            dp: decode_member(&jwk, "dp")?,
+            dq: decode_member(&jwk, "dq")?,
+            qinv: decode_member(&jwk, "qi")?,
+            blinding: Blinding::default(),
+        };
+
+        keypair.validate()?;
+        keypair.add_blinding_entropy(seed)?;
+        Ok(keypair)
+    }
+}
+
+#[cfg(test)]
+mod test_jwk {
+    use super::*;
+    use crypto_math::{string_to_number, test_seed};
+    use rsa::test_keypair;
+
+    // The example key from RFC 7638, section 3.1.
+    static RFC_7638_JWK: &str = r#"{
+        "kty": "RSA",
+        "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+        "e": "AQAB",
+        "alg": "RS256",
+        "kid": "2011-04-29"
+    }"#;
+
+    #[test]
+    fn rfc_7638_thumbprint() {
+        let key = PublicKey::from_jwk(RFC_7638_JWK).unwrap();
+        assert_eq!(key.e(), &string_to_number("65537"));
+        assert_eq!(
+            key.thumbprint(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn public_round_trip() {
+        let key = test_keypair().public_key();
+        let jwk = key.to_jwk();
+
+        let value: Value = serde_json::from_str(&jwk).unwrap();
+        assert_eq!(value["kty"], "RSA");
+        assert_eq!(value["e"], "AQAB");
+        assert_eq!(value["kid"], Value::from(key.thumbprint()));
+        assert!(value.get("d").is_none());
+
+        assert_eq!(PublicKey::from_jwk(&jwk), Ok(key));
+    }
+
+    #[test]
+    fn private_round_trip() {
+        let k = test_keypair();
+        let jwk = k.to_jwk();
+
+        let value: Value = serde_json::from_str(&jwk).unwrap();
+        assert_eq!(value["kid"], Value::from(k.public_key().thumbprint()));
+
+        let parsed = Keypair::from_jwk(&jwk, test_seed()).unwrap();
+        assert_eq!(parsed.to_pkcs1_der(), k.to_pkcs1_der());
+        assert_eq!(PublicKey::from_jwk(&jwk), Ok(k.public_key()));
+    }
+
+    #[test]
+    fn public_jwk_is_not_a_keypair() {
+        assert_eq!(
+            Keypair::from_jwk(RFC_7638_JWK, test_seed()).err(),
+            Some(CryptoError::MalformedKey)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let malformed = Err(CryptoError::MalformedKey);
+        assert_eq!(PublicKey::from_jwk("not json"), malformed);
+        assert_eq!(
+            PublicKey::from_jwk(r#"{"kty":"EC","n":"AQAB","e":"AQAB"}"#),
+            Err(CryptoError::UnsupportedKey)
+        );
+        assert_eq!(
+            PublicKey::from_jwk(r#"{"kty":"RSA","n":"AQAB"}"#),
+            malformed
+        );
+        assert_eq!(
+            PublicKey::from_jwk(r#"{"kty":"RSA","n":"!!","e":"AQAB"}"#),
+            malformed
+        );
+        assert_eq!(PublicKey::from_jwk(r#"["RSA"]"#), malformed);
+    }
+
+    #[test]
+    fn rejects_inconsistent_keypair() {
+        let mut k = test_keypair();
+        k.q += 2;
+        assert_eq!(
+            Keypair::from_jwk(&k.to_jwk(), test_seed()).err(),
+            Some(CryptoError::InvalidKey)
+        );
+    }
+}
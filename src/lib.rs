@@ -1,16 +1,78 @@
 //! Crypto Math is a crate containing basic cryptographic primitives and encryption schemes.
 
+extern crate aes;
+extern crate aes_gcm;
+extern crate base64;
+extern crate cbc;
+extern crate js_sys;
 extern crate num;
 extern crate num_traits;
+extern crate pbkdf2;
 extern crate rand;
+extern crate serde_json;
+#[cfg(test)]
+extern crate sha1;
+extern crate sha2;
 extern crate wasm_bindgen;
 
 #[macro_use]
 extern crate lazy_static;
 
+/// The constant_time module contains modular exponentiation whose running time does not depend on the exponent,
+/// for the private key operations.
+pub mod constant_time;
+
 /// The crypto_math module contains basic crypto math primitives (GCD, modular inverse, etc.).
 pub mod crypto_math;
 
+/// The der module contains a minimal DER encoder and reader for the ASN.1 structures used by key formats.
+pub mod der;
+
+/// The error module contains `CryptoError`, the error type returned throughout the crate.
+pub mod error;
+
+/// The fingerprint module turns public keys into short fingerprints and safety numbers that people can compare
+/// to check that a key really belongs to who they think.
+pub mod fingerprint;
+
+/// The framing module wraps ciphertexts in a small header naming the recipient's key ID and the algorithm, so
+/// that clients can pick out their messages without the full public key in every message.
+pub mod framing;
+
+/// The hybrid module seals messages of any length with AES-256-GCM under a content key that is wrapped with RSA.
+pub mod hybrid;
+
+/// The jwk module converts keys to and from JSON Web Keys, the format used by WebCrypto.
+pub mod jwk;
+
+/// The openssh module reads and writes RSA keys in the formats used by OpenSSH (`ssh-rsa` lines and
+/// `OPENSSH PRIVATE KEY` files).
+pub mod openssh;
+
+/// The padding module contains the message encodings (OAEP and friends) that turn RSA into a secure encryption
+/// scheme.
+pub mod padding;
+
+/// The pem module wraps DER encoded keys in the PEM text armor used by OpenSSL and friends.
+pub mod pem;
+
+/// The pkcs1 module serializes RSA public keys in the PKCS #1 RSAPublicKey format.
+pub mod pkcs1;
+
+/// The pkcs8 module exports and imports whole keypairs as PKCS #8 private keys, optionally protected by a
+/// password.
+pub mod pkcs8;
+
 /// The rsa module contains methods for encryption/decryption of messages utilizng RSA asymmetric,
 /// public key cryptosystem.
 pub mod rsa;
+
+/// The signature module contains methods for signing messages with a RSA keypair and verifying those signatures,
+/// so that readers can tell who sent a message.
+pub mod signature;
+
+/// The spki module serializes RSA public keys as X.509 SubjectPublicKeyInfo, the usual "PUBLIC KEY" format.
+pub mod spki;
+
+/// The validation module checks that generated and imported keys are consistent, following FIPS 186-4.
+pub mod validation;
@@ -0,0 +1,563 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// length in bytes of a SHA-256 digest, the hash used by all padding schemes in this module.
+pub const HASH_LEN: usize = 32;
+
+/// returns 1 if a == b and 0 otherwise, without branching on the values.
+fn ct_eq(a: u8, b: u8) -> u8 {
+    let x = u16::from(a ^ b);
+    (x.wrapping_sub(1) >> 8) as u8 & 1
+}
+
+/// returns 1 if both slices hold the same bytes and 0 otherwise. Only the lengths are compared with a branch.
+fn ct_eq_slices(a: &[u8], b: &[u8]) -> u8 {
+    if a.len() != b.len() {
+        return 0;
+    }
+
+    let mut difference = 0;
+    for (x, y) in a.iter().zip(b) {
+        difference |= x ^ y;
+    }
+
+    ct_eq(difference, 0)
+}
+
+/// returns 1 if a < b and 0 otherwise, without branching on the values. Both must be below 2^(usize::BITS - 1).
+fn ct_less_than(a: usize, b: usize) -> u8 {
+    (a.wrapping_sub(b) >> (usize::BITS - 1)) as u8
+}
+
+/// picks a when flag is 1 and b when flag is 0, without branching on flag.
+fn ct_select(flag: u8, a: usize, b: usize) -> usize {
+    let mask = (flag as usize).wrapping_neg();
+    (a & mask) | (b & !mask)
+}
+
+/// mask generation function MGF1 with SHA-256. Stretches a seed into a mask of the requested length.
+///
+/// ## Reference
+/// See [RFC 8017, appendix B.2.1](https://tools.ietf.org/html/rfc8017#appendix-B.2.1).
+pub fn mgf1_sha256(seed: &[u8], mask_len: usize) -> Vec<u8> {
+    mgf1::<Sha256>(seed, mask_len)
+}
+
+/// MGF1 with any hash function.
+fn mgf1<D: Digest>(seed: &[u8], mask_len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(mask_len + <D as Digest>::output_size());
+    let mut counter: u32 = 0;
+
+    while mask.len() < mask_len {
+        let mut hasher = D::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        mask.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+
+    mask.truncate(mask_len);
+    mask
+}
+
+#[cfg(test)]
+mod test_mgf1_sha256 {
+    use super::*;
+
+    #[test]
+    fn known_answer() {
+        let mask = mgf1_sha256(b"foo", 40);
+        let expected =
+            "3bdaba83cff13337b323ac383ca3995863e922f511b931b9efd4e0118cfc70f08678390d67e3c12d";
+        let hex: String = mask.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, expected);
+    }
+
+    #[test]
+    fn empty_mask() {
+        assert_eq!(mgf1_sha256(b"foo", 0), Vec::<u8>::new());
+    }
+}
+
+/// the longest message that fits in a single EME-OAEP block for a k byte modulo.
+pub fn oaep_max_message_len(k: usize) -> usize {
+    k.saturating_sub(2 * HASH_LEN + 2)
+}
+
+/// encodes a message into a k byte EME-OAEP block using SHA-256 and MGF1. The seed must be HASH_LEN random
+/// bytes. Returns None if the message is too long for the block or the seed has the wrong length.
+///
+/// ## Reference
+/// See [RFC 8017, section 7.1.1](https://tools.ietf.org/html/rfc8017#section-7.1.1), step 2.
+pub fn oaep_encode(message: &[u8], label: &[u8], k: usize, seed: &[u8]) -> Option<Vec<u8>> {
+    oaep_encode_with::<Sha256>(message, label, k, seed)
+}
+
+/// `oaep_encode` with any hash function for the label and MGF1. The seed must be as long as a digest.
+fn oaep_encode_with<D: Digest>(
+    message: &[u8],
+    label: &[u8],
+    k: usize,
+    seed: &[u8],
+) -> Option<Vec<u8>> {
+    let h_len = <D as Digest>::output_size();
+    if seed.len() != h_len || k < 2 * h_len + 2 || message.len() > k - 2 * h_len - 2 {
+        return None;
+    }
+
+    // DB = lHash || PS || 0x01 || M
+    let mut db = D::digest(label).to_vec();
+    db.resize(k - message.len() - h_len - 2, 0);
+    db.push(1);
+    db.extend_from_slice(message);
+
+    let db_mask = mgf1::<D>(seed, k - h_len - 1);
+    let masked_db: Vec<u8> = db.iter().zip(&db_mask).map(|(x, y)| x ^ y).collect();
+
+    let seed_mask = mgf1::<D>(&masked_db, h_len);
+    let masked_seed: Vec<u8> = seed.iter().zip(&seed_mask).map(|(x, y)| x ^ y).collect();
+
+    // EM = 0x00 || maskedSeed || maskedDB
+    let mut em = Vec::with_capacity(k);
+    em.push(0);
+    em.extend_from_slice(&masked_seed);
+    em.extend_from_slice(&masked_db);
+    Some(em)
+}
+
+/// decodes a k byte EME-OAEP block back into the message. All checks are combined without branching on secret
+/// data, and every kind of failure returns the same None, so that an attacker cannot learn which check failed.
+///
+/// ## Reference
+/// See [RFC 8017, section 7.1.2](https://tools.ietf.org/html/rfc8017#section-7.1.2), step 3.
+pub fn oaep_decode(em: &[u8], label: &[u8], k: usize) -> Option<Vec<u8>> {
+    oaep_decode_with::<Sha256>(em, label, k)
+}
+
+/// `oaep_decode` with any hash function for the label and MGF1.
+fn oaep_decode_with<D: Digest>(em: &[u8], label: &[u8], k: usize) -> Option<Vec<u8>> {
+    let h_len = <D as Digest>::output_size();
+    if k < 2 * h_len + 2 || em.len() != k {
+        return None;
+    }
+
+    let masked_seed = &em[1..=h_len];
+    let masked_db = &em[h_len + 1..];
+
+    let seed_mask = mgf1::<D>(masked_db, h_len);
+    let seed: Vec<u8> = masked_seed
+        .iter()
+        .zip(&seed_mask)
+        .map(|(x, y)| x ^ y)
+        .collect();
+
+    let db_mask = mgf1::<D>(&seed, k - h_len - 1);
+    let db: Vec<u8> = masked_db.iter().zip(&db_mask).map(|(x, y)| x ^ y).collect();
+
+    let mut valid = ct_eq(em[0], 0) & ct_eq_slices(&db[..h_len], &D::digest(label));
+
+    // Scan PS || 0x01 || M for the first 0x01, remembering whether a non-zero byte came before it.
+    let mut looking = 1;
+    let mut separator = 0;
+    for (i, &byte) in db[h_len..].iter().enumerate() {
+        let is_zero = ct_eq(byte, 0);
+        let is_one = ct_eq(byte, 1);
+
+        // While still looking, every byte must be either padding (0x00) or the separator (0x01).
+        valid &= (looking & !(is_zero | is_one) & 1) ^ 1;
+        separator = ct_select(looking & is_one, i, separator);
+        looking &= is_one ^ 1;
+    }
+    valid &= looking ^ 1;
+
+    if valid == 1 {
+        Some(db[h_len + separator + 1..].to_vec())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_oaep {
+    use super::*;
+    use sha1::Sha1;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // `openssl pkeyutl -encrypt -pkeyopt rsa_padding_mode:oaep -pkeyopt rsa_oaep_md:sha1` of "Hello World!" to the
+    // key from `rsa::test_keypair`, first without a label and then with the label "chat!", decrypted again with
+    // `-pkeyopt rsa_padding_mode:none` to get the encoded message. The seed is the one recovered from it.
+    static OPENSSL_SHA1_VECTORS: &[(&[u8], &str, &str)] = &[
+        (
+            b"",
+            "cc8d414fd1b15940831bed909d8aa87ac52d55b7",
+            "0093887e58861417ab267c834f4590da45a8488b0468bf1e466bc05bd6f0e8eab8c73878028b621d300d5ba0afee4245b0\
+             9cc4e2957ce3f0327f3f102c674588265ce249e4a546d0c600774988190ca0f200fa8829d9bd06950b77675e6e6f0293f6\
+             ea1b2e499ab2b72ef9887575cd0ced99336ae03a3acaf6b08c801fdbd674",
+        ),
+        (
+            b"chat!",
+            "c727bd56fddd8db4ce2b7e33e19727ac5646d6eb",
+            "004849f15a5ce6059ca44c17c87c7e196b08bb46bcfa1e6aceb5115e1caf4b02a3b2b8671b537f44a4706caaf26fcb17ac\
+             0683f695c67012b6640e25ac3f00cc61a028731bb0a5170903456db7f8e4a5fb39c9e67885bc75452491e8070f12099317\
+             004fbed989557b7fa61633315ba180ead198cade84df21e19150db78a607",
+        ),
+    ];
+
+    #[test]
+    fn matches_openssl_sha1() {
+        for &(label, seed, em) in OPENSSL_SHA1_VECTORS {
+            let em = from_hex(em);
+            let encoded = oaep_encode_with::<Sha1>(b"Hello World!", label, 128, &from_hex(seed));
+            assert_eq!(encoded, Some(em.clone()));
+            assert_eq!(
+                oaep_decode_with::<Sha1>(&em, label, 128),
+                Some(b"Hello World!".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let seed = [7; HASH_LEN];
+        let em = oaep_encode(b"Hello World!", b"", 128, &seed).unwrap();
+        assert_eq!(em.len(), 128);
+        assert_eq!(oaep_decode(&em, b"", 128), Some(b"Hello World!".to_vec()));
+    }
+
+    #[test]
+    fn empty_and_longest_message() {
+        let seed = [7; HASH_LEN];
+        let longest = vec![42; oaep_max_message_len(128)];
+
+        let em = oaep_encode(b"", b"", 128, &seed).unwrap();
+        assert_eq!(oaep_decode(&em, b"", 128), Some(vec![]));
+
+        let em = oaep_encode(&longest, b"", 128, &seed).unwrap();
+        assert_eq!(oaep_decode(&em, b"", 128), Some(longest));
+    }
+
+    #[test]
+    fn message_too_long() {
+        let seed = [7; HASH_LEN];
+        let message = vec![42; oaep_max_message_len(128) + 1];
+        assert_eq!(oaep_encode(&message, b"", 128, &seed), None);
+    }
+
+    #[test]
+    fn modulo_too_small() {
+        let seed = [7; HASH_LEN];
+        assert_eq!(oaep_encode(b"", b"", 64, &seed), None);
+    }
+
+    #[test]
+    fn wrong_label() {
+        let seed = [7; HASH_LEN];
+        let em = oaep_encode(b"Hello World!", b"one", 128, &seed).unwrap();
+        assert_eq!(oaep_decode(&em, b"two", 128), None);
+    }
+
+    #[test]
+    fn corrupted_blocks() {
+        let seed = [7; HASH_LEN];
+        let em = oaep_encode(b"Hello World!", b"", 128, &seed).unwrap();
+
+        for i in 0..em.len() {
+            let mut corrupted = em.clone();
+            corrupted[i] ^= 0x80;
+            assert_eq!(oaep_decode(&corrupted, b"", 128), None);
+        }
+    }
+}
+
+/// the longest message that fits in a single EME-PKCS1-v1_5 block for a k byte modulo.
+pub fn pkcs1v15_max_message_len(k: usize) -> usize {
+    k.saturating_sub(11)
+}
+
+/// encodes a message into a k byte EME-PKCS1-v1_5 block, drawing the non-zero padding bytes from rng. Returns None
+/// if the message is too long for the block.
+///
+/// ## Reference
+/// See [RFC 8017, section 7.2.1](https://tools.ietf.org/html/rfc8017#section-7.2.1), step 2.
+pub fn pkcs1v15_encode<R: Rng>(message: &[u8], k: usize, rng: &mut R) -> Option<Vec<u8>> {
+    if k < 11 || message.len() > pkcs1v15_max_message_len(k) {
+        return None;
+    }
+
+    // EM = 0x00 || 0x02 || PS || 0x00 || M
+    let mut em = Vec::with_capacity(k);
+    em.push(0);
+    em.push(2);
+
+    while em.len() < k - message.len() - 1 {
+        let byte: u8 = rng.gen();
+        if byte != 0 {
+            em.push(byte);
+        }
+    }
+
+    em.push(0);
+    em.extend_from_slice(message);
+    Some(em)
+}
+
+/// decodes a k byte EME-PKCS1-v1_5 block back into the message. As with `oaep_decode`, the checks run without
+/// branching on secret data and every failure returns the same None, which keeps Bleichenbacher-style padding
+/// oracles from telling the failures apart.
+///
+/// ## Reference
+/// See [RFC 8017, section 7.2.2](https://tools.ietf.org/html/rfc8017#section-7.2.2), step 3.
+pub fn pkcs1v15_decode(em: &[u8], k: usize) -> Option<Vec<u8>> {
+    if k < 11 || em.len() != k {
+        return None;
+    }
+
+    let mut valid = ct_eq(em[0], 0) & ct_eq(em[1], 2);
+
+    // Scan PS || 0x00 || M for the first 0x00.
+    let mut looking = 1;
+    let mut separator = 0;
+    for (i, &byte) in em[2..].iter().enumerate() {
+        let is_zero = ct_eq(byte, 0);
+
+        separator = ct_select(looking & is_zero, i, separator);
+        looking &= is_zero ^ 1;
+    }
+    valid &= looking ^ 1;
+
+    // PS must be at least eight bytes long.
+    valid &= ct_less_than(separator, 8) ^ 1;
+
+    if valid == 1 {
+        Some(em[separator + 3..].to_vec())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_pkcs1v15 {
+    use super::*;
+    use crypto_math::{from_slice, test_seed};
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn round_trip() {
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(test_seed()).unwrap());
+        let em = pkcs1v15_encode(b"Hello World!", 128, &mut rng).unwrap();
+
+        assert_eq!(em.len(), 128);
+        assert!(em[2..128 - 13].iter().all(|&b| b != 0));
+        assert_eq!(pkcs1v15_decode(&em, 128), Some(b"Hello World!".to_vec()));
+    }
+
+    #[test]
+    fn empty_and_longest_message() {
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(test_seed()).unwrap());
+        let longest = vec![42; pkcs1v15_max_message_len(128)];
+
+        let em = pkcs1v15_encode(b"", 128, &mut rng).unwrap();
+        assert_eq!(pkcs1v15_decode(&em, 128), Some(vec![]));
+
+        let em = pkcs1v15_encode(&longest, 128, &mut rng).unwrap();
+        assert_eq!(pkcs1v15_decode(&em, 128), Some(longest));
+    }
+
+    #[test]
+    fn message_too_long() {
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(test_seed()).unwrap());
+        let message = vec![42; pkcs1v15_max_message_len(128) + 1];
+        assert_eq!(pkcs1v15_encode(&message, 128, &mut rng), None);
+    }
+
+    #[test]
+    fn malformed_blocks() {
+        let mut good = vec![0, 2];
+        good.extend_from_slice(&[0xff; 8]);
+        good.push(0);
+        good.extend_from_slice(b"hi");
+        assert_eq!(pkcs1v15_decode(&good, 13), Some(b"hi".to_vec()));
+
+        let mut bad_first_byte = good.clone();
+        bad_first_byte[0] = 1;
+        assert_eq!(pkcs1v15_decode(&bad_first_byte, 13), None);
+
+        let mut bad_block_type = good.clone();
+        bad_block_type[1] = 1;
+        assert_eq!(pkcs1v15_decode(&bad_block_type, 13), None);
+
+        let mut short_padding = good.clone();
+        short_padding[9] = 0;
+        assert_eq!(pkcs1v15_decode(&short_padding, 13), None);
+
+        let no_separator = vec![
+            0, 2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ];
+        assert_eq!(pkcs1v15_decode(&no_separator, 13), None);
+    }
+}
+
+/// the DER encoded DigestInfo prefix that identifies SHA-256 in EMSA-PKCS1-v1_5.
+static SHA256_DIGEST_INFO: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// encodes the SHA-256 hash of a message into an EMSA-PSS block for a modulo of em_bits + 1 bits, using the given
+/// salt. Returns None if the block is too short for the hash and salt.
+///
+/// ## Reference
+/// See [RFC 8017, section 9.1.1](https://tools.ietf.org/html/rfc8017#section-9.1.1).
+pub fn pss_encode(message: &[u8], em_bits: usize, salt: &[u8]) -> Option<Vec<u8>> {
+    let em_len = em_bits.div_ceil(8);
+    if em_len < HASH_LEN + salt.len() + 2 {
+        return None;
+    }
+
+    let h = pss_hash(&Sha256::digest(message), salt);
+
+    // DB = PS || 0x01 || salt
+    let mut db = vec![0; em_len - salt.len() - HASH_LEN - 2];
+    db.push(1);
+    db.extend_from_slice(salt);
+
+    let db_mask = mgf1_sha256(&h, em_len - HASH_LEN - 1);
+    let mut masked_db: Vec<u8> = db.iter().zip(&db_mask).map(|(x, y)| x ^ y).collect();
+    masked_db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    // EM = maskedDB || H || 0xbc
+    let mut em = masked_db;
+    em.extend_from_slice(&h);
+    em.push(0xbc);
+    Some(em)
+}
+
+/// checks that an EMSA-PSS block matches the SHA-256 hash of a message, for a modulo of em_bits + 1 bits and a
+/// salt of salt_len bytes.
+///
+/// ## Reference
+/// See [RFC 8017, section 9.1.2](https://tools.ietf.org/html/rfc8017#section-9.1.2).
+pub fn pss_verify(message: &[u8], em: &[u8], em_bits: usize, salt_len: usize) -> bool {
+    let em_len = em_bits.div_ceil(8);
+    if em.len() != em_len || em_len < HASH_LEN + salt_len + 2 || em[em_len - 1] != 0xbc {
+        return false;
+    }
+
+    let masked_db = &em[..em_len - HASH_LEN - 1];
+    let h = &em[em_len - HASH_LEN - 1..em_len - 1];
+
+    let top_bits = 0xff >> (8 * em_len - em_bits);
+    if masked_db[0] & !top_bits != 0 {
+        return false;
+    }
+
+    let db_mask = mgf1_sha256(h, em_len - HASH_LEN - 1);
+    let mut db: Vec<u8> = masked_db.iter().zip(&db_mask).map(|(x, y)| x ^ y).collect();
+    db[0] &= top_bits;
+
+    let padding_len = em_len - HASH_LEN - salt_len - 2;
+    if db[..padding_len].iter().any(|&b| b != 0) || db[padding_len] != 1 {
+        return false;
+    }
+
+    let salt = &db[db.len() - salt_len..];
+    pss_hash(&Sha256::digest(message), salt) == h
+}
+
+/// computes H = Hash(0x00 * 8 || mHash || salt), the value at the heart of EMSA-PSS.
+fn pss_hash(m_hash: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update([0; 8]);
+    hasher.update(m_hash);
+    hasher.update(salt);
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod test_pss {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let salt = [3; HASH_LEN];
+        let em = pss_encode(b"Hello World!", 1023, &salt).unwrap();
+
+        assert_eq!(em.len(), 128);
+        assert!(pss_verify(b"Hello World!", &em, 1023, HASH_LEN));
+        assert!(!pss_verify(b"Hello World?", &em, 1023, HASH_LEN));
+    }
+
+    #[test]
+    fn clears_top_bits() {
+        let salt = [3; HASH_LEN];
+        let em = pss_encode(b"Hello World!", 1020, &salt).unwrap();
+
+        assert_eq!(em[0] & 0xf0, 0);
+        assert!(pss_verify(b"Hello World!", &em, 1020, HASH_LEN));
+    }
+
+    #[test]
+    fn block_too_short() {
+        let salt = [3; HASH_LEN];
+        assert_eq!(pss_encode(b"Hello World!", 511, &salt), None);
+    }
+
+    #[test]
+    fn corrupted_blocks() {
+        let salt = [3; HASH_LEN];
+        let em = pss_encode(b"Hello World!", 1023, &salt).unwrap();
+
+        for i in 0..em.len() {
+            let mut corrupted = em.clone();
+            corrupted[i] ^= 0x01;
+            assert!(!pss_verify(b"Hello World!", &corrupted, 1023, HASH_LEN));
+        }
+    }
+}
+
+/// encodes the SHA-256 hash of a message into an em_len byte EMSA-PKCS1-v1_5 block. Returns None if the block is
+/// too short.
+///
+/// ## Reference
+/// See [RFC 8017, section 9.2](https://tools.ietf.org/html/rfc8017#section-9.2).
+pub fn pkcs1v15_signature_encode(message: &[u8], em_len: usize) -> Option<Vec<u8>> {
+    let t_len = SHA256_DIGEST_INFO.len() + HASH_LEN;
+    if em_len < t_len + 11 {
+        return None;
+    }
+
+    // EM = 0x00 || 0x01 || PS || 0x00 || T
+    let mut em = vec![0, 1];
+    em.resize(em_len - t_len - 1, 0xff);
+    em.push(0);
+    em.extend_from_slice(SHA256_DIGEST_INFO);
+    em.extend_from_slice(&Sha256::digest(message));
+    Some(em)
+}
+
+#[cfg(test)]
+mod test_pkcs1v15_signature {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let em = pkcs1v15_signature_encode(b"", 64).unwrap();
+
+        assert_eq!(&em[..2], &[0, 1]);
+        assert!(em[2..12].iter().all(|&b| b == 0xff));
+        assert_eq!(em[12], 0);
+        assert_eq!(&em[13..32], SHA256_DIGEST_INFO);
+        assert_eq!(&em[32..], &Sha256::digest(b"")[..]);
+    }
+
+    #[test]
+    fn block_too_short() {
+        assert_eq!(pkcs1v15_signature_encode(b"", 61), None);
+    }
+}
@@ -0,0 +1,83 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// wraps DER bytes in PEM armor with the given label, e.g. "PUBLIC KEY", using 64 character lines.
+///
+/// ## Reference
+/// See [RFC 7468](https://tools.ietf.org/html/rfc7468).
+pub fn encode(label: &str, der: &[u8]) -> String {
+    encode_with_width(label, der, 64)
+}
+
+/// wraps bytes in PEM armor like `encode`, but with lines of the given width. OpenSSH, for one, uses 70.
+pub fn encode_with_width(label: &str, der: &[u8], width: usize) -> String {
+    let body = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+
+    for line in body.as_bytes().chunks(width) {
+        // base64 output is ASCII, so every chunk is valid UTF-8.
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// removes PEM armor with the given label and returns the DER bytes inside. Surrounding whitespace and line
+/// breaks inside the body are ignored. Returns None if the label does not match or the body is not base64.
+pub fn decode(label: &str, pem: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let pem = pem.trim();
+    if !pem.starts_with(&begin) || !pem.ends_with(&end) || pem.len() < begin.len() + end.len() {
+        return None;
+    }
+
+    let body: String = pem[begin.len()..pem.len() - end.len()]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    STANDARD.decode(body).ok()
+}
+
+/// the label of a PEM document, i.e. "PUBLIC KEY" for one starting with "-----BEGIN PUBLIC KEY-----".
+pub fn label(pem: &str) -> Option<&str> {
+    let rest = pem.trim_start().strip_prefix("-----BEGIN ")?;
+    let end = rest.find("-----")?;
+    Some(&rest[..end])
+}
+
+#[cfg(test)]
+mod test_pem {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let der: Vec<u8> = (0..100).collect();
+        let pem = encode("TEST", &der);
+
+        assert!(pem.starts_with("-----BEGIN TEST-----\n"));
+        assert!(pem.ends_with("-----END TEST-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= 64));
+        assert_eq!(label(&pem), Some("TEST"));
+        assert_eq!(decode("TEST", &pem), Some(der));
+    }
+
+    #[test]
+    fn wrong_label() {
+        let pem = encode("TEST", &[1, 2, 3]);
+        assert_eq!(decode("OTHER", &pem), None);
+    }
+
+    #[test]
+    fn garbage() {
+        assert_eq!(
+            decode("TEST", "-----BEGIN TEST-----\n!!!!\n-----END TEST-----"),
+            None
+        );
+        assert_eq!(decode("TEST", "-----BEGIN TEST-----"), None);
+        assert_eq!(label("not pem"), None);
+    }
+}
@@ -1,6 +1,26 @@
-use crypto_math::{generate_prime, lcm, mod_inverse, number_to_string, string_to_number};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use constant_time;
+use crypto_math::{
+    bytes_to_number, from_slice, generate_prime, generate_rsa_prime, lcm, mod_inverse,
+    number_to_bytes, number_to_string, string_to_number, ModRing, MontgomeryContext, PrimalityTest,
+    DEFAULT_SECURITY_BITS,
+};
+use error::CryptoError;
 use num::bigint::BigInt;
 use num_traits::ToPrimitive;
+use openssh;
+use padding::{
+    oaep_decode, oaep_encode, oaep_max_message_len, pkcs1v15_decode, pkcs1v15_encode,
+    pkcs1v15_max_message_len, HASH_LEN,
+};
+use pem;
+use pkcs1;
+use rand::{Rng, SeedableRng, StdRng};
+use sha2::{Digest, Sha256};
+use spki;
+use std::cell::RefCell;
+use std::fmt;
 use wasm_bindgen::prelude::*;
 
 // Why lazy_static you may ask? Well, for one, try to compile this without lazy_static. You will
@@ -13,73 +33,713 @@ lazy_static! {
     static ref TWO: BigInt = string_to_number("2");
 }
 
+/// modulus sizes (in bits) accepted by `KeypairBuilder`.
+pub static SUPPORTED_MODULUS_BITS: &[usize] = &[1024, 2048, 3072, 4096];
+
+/// candidates `KeypairBuilder` tries per bit of each prime unless told otherwise. About one in b ln 2 / 2 odd
+/// numbers near 2^b is prime, so 10 b candidates hold some 29 primes on average whatever the size, and even with
+/// e = 3, which rules out half of them, the search fails with probability around e^-14. Only candidates without
+/// small factors count as tries (see `generate_rsa_prime`), so the real margin is wider still. A fixed budget would
+/// not do: 1000 odd candidates miss a 1024-bit prime about 6% of the time.
+pub const PRIME_TRIES_PER_BIT: usize = 10;
+
+/// stores the public half (e, n) of an RSA keypair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKey {
+    /// Public exponent
+    e: BigInt,
+    /// Modulo
+    n: BigInt,
+}
+
+impl PublicKey {
+    /// creates a public key from its exponent and modulo.
+    pub fn new(e: BigInt, n: BigInt) -> PublicKey {
+        PublicKey { e, n }
+    }
+
+    /// parses a public key as output by `Keypair::public_key_display_wasm`, that is n and e as radix 32 strings
+    /// separated by a colon. A lone n (the older format) is read with the default exponent 65537. PEM encoded
+    /// "PUBLIC KEY" and "RSA PUBLIC KEY" documents, JSON Web Keys and OpenSSH `ssh-rsa` lines are accepted too.
+    /// The key must pass `PublicKey::validate`.
+    pub fn parse(s: &str) -> Result<PublicKey, CryptoError> {
+        if s.trim_start().starts_with('{') {
+            return PublicKey::from_jwk(s);
+        }
+        if s.trim_start().starts_with(openssh::SSH_RSA) {
+            return PublicKey::from_openssh(s);
+        }
+
+        match pem::label(s) {
+            Some(label) if label == spki::PUBLIC_KEY_LABEL => return PublicKey::from_spki_pem(s),
+            Some(label) if label == pkcs1::PUBLIC_KEY_LABEL => return PublicKey::from_pkcs1_pem(s),
+            Some(_) => return Err(CryptoError::UnsupportedKey),
+            None => {}
+        }
+
+        let mut parts = s.trim().splitn(2, ':');
+        let parse_radix_32 =
+            |part: &str| BigInt::parse_bytes(part.as_bytes(), 32).ok_or(CryptoError::MalformedKey);
+
+        let n = parse_radix_32(parts.next().unwrap_or(""))?;
+        let e = match parts.next() {
+            Some(e) => parse_radix_32(e)?,
+            None => string_to_number("65537"),
+        };
+
+        let key = PublicKey { e, n };
+        key.validate()?;
+        Ok(key)
+    }
+
+    /// the public exponent.
+    pub fn e(&self) -> &BigInt {
+        &self.e
+    }
+
+    /// the modulo.
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    /// the length of the modulo in bytes, which is also the length of every ciphertext block.
+    pub fn size(&self) -> usize {
+        self.n.bits().div_ceil(8)
+    }
+
+    /// the RSA encryption primitive, m^e mod n. Fails if m is out of range.
+    ///
+    /// ## Reference
+    /// See RSAEP in [RFC 8017, section 5.1.1](https://tools.ietf.org/html/rfc8017#section-5.1.1).
+    pub(crate) fn rsaep(&self, m: &BigInt) -> Result<BigInt, CryptoError> {
+        if *m < *ZERO || *m >= self.n {
+            return Err(CryptoError::MalformedCiphertext);
+        }
+
+        let ring = MontgomeryContext::new(&self.n).ok_or(CryptoError::InvalidKey)?;
+        Ok(ring.modpow(m, &self.e))
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.n.to_str_radix(32), self.e.to_str_radix(32))
+    }
+}
+
+#[cfg(test)]
+mod test_public_key {
+    use super::*;
+
+    #[test]
+    fn display_and_parse() {
+        let key = PublicKey::new(string_to_number("3"), string_to_number("3233"));
+        assert_eq!(key.to_string(), "351:3");
+
+        let key = test_keypair().public_key();
+        assert_eq!(PublicKey::parse(&key.to_string()), Ok(key));
+    }
+
+    #[test]
+    fn parse_without_exponent() {
+        let n = test_keypair().n;
+        let key = PublicKey::parse(&n.to_str_radix(32)).unwrap();
+        assert_eq!(key.n(), &n);
+        assert_eq!(key.e(), &string_to_number("65537"));
+    }
+
+    #[test]
+    fn parse_validates() {
+        // 3233 = 53 * 61
+        assert_eq!(PublicKey::parse("351:3"), Err(CryptoError::InvalidKey));
+
+        let key = PublicKey::new(string_to_number("3"), string_to_number("3233"));
+        assert_eq!(
+            PublicKey::parse(&key.to_spki_pem()),
+            Err(CryptoError::InvalidKey)
+        );
+        assert_eq!(
+            PublicKey::parse(&key.to_jwk()),
+            Err(CryptoError::InvalidKey)
+        );
+        assert_eq!(
+            PublicKey::parse(&key.to_openssh("")),
+            Err(CryptoError::InvalidKey)
+        );
+    }
+
+    #[test]
+    fn parse_garbage() {
+        assert_eq!(
+            PublicKey::parse("not a key!"),
+            Err(CryptoError::MalformedKey)
+        );
+        assert_eq!(PublicKey::parse("351:"), Err(CryptoError::MalformedKey));
+        assert_eq!(
+            PublicKey::parse("-----BEGIN CERTIFICATE-----"),
+            Err(CryptoError::UnsupportedKey)
+        );
+    }
+
+    #[test]
+    fn parse_pem() {
+        let key = test_keypair().public_key();
+        assert_eq!(PublicKey::parse(&key.to_spki_pem()), Ok(key.clone()));
+        assert_eq!(PublicKey::parse(&key.to_pkcs1_pem()), Ok(key));
+    }
+
+    #[test]
+    fn parse_jwk() {
+        let key = test_keypair().public_key();
+        assert_eq!(PublicKey::parse(&key.to_jwk()), Ok(key));
+    }
+
+    #[test]
+    fn parse_openssh() {
+        let key = test_keypair().public_key();
+        assert_eq!(PublicKey::parse(&key.to_openssh("bob@laptop")), Ok(key));
+    }
+}
+
 /// stores the information for a given RSA keypair.
 #[wasm_bindgen]
 #[derive(Debug)]
 pub struct Keypair {
-    /// Private key
-    d: String,
+    /// Private key. Decryption uses the CRT values below, but d is kept so the key stays complete.
+    pub(crate) d: BigInt,
+    /// Public exponent
+    pub(crate) e: BigInt,
     /// Modulo (both public and private)
-    n: String,
+    pub(crate) n: BigInt,
+    /// First prime factor of n
+    pub(crate) p: BigInt,
+    /// Second prime factor of n
+    pub(crate) q: BigInt,
+    /// d mod (p - 1), the private exponent for the CRT half modulo p
+    pub(crate) dp: BigInt,
+    /// d mod (q - 1), the private exponent for the CRT half modulo q
+    pub(crate) dq: BigInt,
+    /// q^-1 mod p, used to recombine the two CRT halves
+    pub(crate) qinv: BigInt,
+    /// Random numbers for blinding the private key operations
+    pub(crate) blinding: Blinding,
 }
 
+/// the random number generator behind the blinding factors of a keypair. It is empty until outside randomness is
+/// mixed in with `Keypair::add_blinding_entropy`, which generated keys do with their seeds and imported keys with
+/// the seed given to the import, and private key operations fail with `CryptoError::EntropyRequired` until then: a
+/// generator seeded from the key alone would give the same factors every time the key is loaded. Each use moves it
+/// on, so that no two operations share a factor.
+#[derive(Debug, Default)]
+pub(crate) struct Blinding(RefCell<Option<StdRng>>);
+
 #[wasm_bindgen]
 impl Keypair {
-    /// randomly generates a new keypair based on two seeds.
-    pub fn new(seed_one: &[u8], seed_two: &[u8]) -> Keypair {
+    /// randomly generates a new keypair based on two seeds of at least 32 bytes each.
+    pub fn new(seed_one: &[u8], seed_two: &[u8]) -> Result<Keypair, CryptoError> {
         // Hardcoded to 256-bits with 1000 tries for now
-        let q_num = generate_prime(256, 1000, &seed_one).unwrap();
+        let q_num = generate_prime(256, 1000, DEFAULT_SECURITY_BITS, seed_one)?;
 
         // Hardcoded to 256-bits with 1000 tries for now
-        let p_num = generate_prime(256, 1000, &seed_two).unwrap();
+        let p_num = generate_prime(256, 1000, DEFAULT_SECURITY_BITS, seed_two)?;
 
-        let n_num = &p_num * &q_num;
-
-        let p_minus_one = &p_num - &*ONE;
-        let q_minus_one = &q_num - &*ONE;
-
-        let phi_num = lcm(&p_minus_one, &q_minus_one);
         // Hard Code in 65537.
         // Ref: https://www.reddit.com/r/crypto/comments/6363di/how_do_computers_choose_the_rsa_value_for_e/
         let e_num = string_to_number("65537");
 
-        let mut d_num = mod_inverse(&e_num, &phi_num).unwrap();
+        let keypair = Keypair::from_primes(&p_num, &q_num, &e_num)?;
+        keypair.validate()?;
+        keypair.seed_blinding(seed_one, seed_two)?;
+        Ok(keypair)
+    }
 
-        if d_num < *ZERO {
-            d_num += &phi_num;
-        }
+    /// mixes a seed of at least 32 bytes, e.g. from `crypto.getRandomValues`, into the random number generator
+    /// that blinds the private key operations. Generated and imported keys are already seeded from the seeds they
+    /// were created with, and more randomness can be mixed in at any time.
+    pub fn add_blinding_entropy(&self, seed: &[u8]) -> Result<(), CryptoError> {
+        let seed = from_slice(seed)?;
+        let mut rng = self.blinding.0.borrow_mut();
+        let rng = rng.get_or_insert_with(|| self.blinding_rng());
 
-        Keypair {
-            d: number_to_string(&d_num),
-            n: number_to_string(&n_num),
-        }
+        let mut current = [0; 32];
+        rng.fill(&mut current);
+        let mut mixed = Sha256::new();
+        mixed.update(current);
+        mixed.update(seed);
+        *rng = SeedableRng::from_seed(mixed.finalize().into());
+        Ok(())
     }
 
     /// nicely outputs a formatted public key for use in the javascript code.
     /// improved since 0.2.0. Now outputs just n as a radix 32 string similar
     /// to how it is done here: http://gauss.ececs.uc.edu/Courses/c653/project/radix_32.html
+    /// The public exponent now follows n, separated by a colon (see `PublicKey::parse`).
     pub fn public_key_display_wasm(&self) -> String {
-        format!("{}", string_to_number(&self.n).to_str_radix(32))
+        self.public_key().to_string()
+    }
+
+    /// given a ciphertext in the legacy format of `encrypt_legacy` (without its leading comma), attempts to decrypt
+    /// based on the private key and modulo from this keypair. Performs simple decryption based on RSA algorithm:
+    /// every block decrypts to one byte of the message, and the bytes are decoded as UTF-8. Fails with
+    /// `CryptoError::DecryptionFailed` if a block does not decrypt to a single byte (e.g. it was meant for another
+    /// key) and with `CryptoError::InvalidUtf8` if the bytes are not UTF-8. Only for messages from older clients;
+    /// new code should use `decrypt_envelope` or `decrypt_bytes`. Exported to JavaScript as `decrypt`, the name
+    /// existing clients call.
+    #[wasm_bindgen(js_name = decrypt)]
+    pub fn decrypt_legacy(&self, ciphertext: &str) -> Result<String, CryptoError> {
+        let mut message = Vec::new();
+
+        for c in parse_blocks(ciphertext)? {
+            let byte = self
+                .rsadp(&c)?
+                .to_u8()
+                .ok_or(CryptoError::DecryptionFailed)?;
+            message.push(byte);
+        }
+
+        utf8(message)
+    }
+
+    /// given a ciphertext produced by `encrypt_oaep`, decrypts and decodes each block with RSAES-OAEP. Fails with
+    /// `CryptoError::DecryptionFailed` if any block does not decode; the reason is deliberately not reported.
+    pub fn decrypt_oaep(&self, ciphertext: &str) -> Result<String, CryptoError> {
+        let blocks = parse_blocks(ciphertext)?;
+        let message = self.decrypt_blocks(&blocks, |em, k| oaep_decode(em, b"", k))?;
+        utf8(message)
+    }
+
+    /// given a ciphertext produced by `encrypt_pkcs1v15` (or by other RSAES-PKCS1-v1_5 implementations), decrypts
+    /// and decodes each block. Fails with `CryptoError::DecryptionFailed` if any block does not decode; the reason
+    /// is deliberately not reported. That includes messages that are not UTF-8: unlike OAEP, PKCS #1 v1.5 padding
+    /// does not reject forgeries, so telling the two failures apart would reveal which blocks had valid padding,
+    /// the oracle of Bleichenbacher's attack.
+    ///
+    /// ## Reference
+    /// See Bleichenbacher, [Chosen Ciphertext Attacks Against Protocols Based on the RSA Encryption Standard PKCS
+    /// #1](https://doi.org/10.1007/BFb0055716).
+    pub fn decrypt_pkcs1v15(&self, ciphertext: &str) -> Result<String, CryptoError> {
+        let blocks = parse_blocks(ciphertext)?;
+        let message = self.decrypt_blocks(&blocks, pkcs1v15_decode)?;
+        String::from_utf8(message).map_err(|_| CryptoError::DecryptionFailed)
+    }
+
+    /// given a ciphertext produced by `encrypt_bytes`, that is one or more RSAES-OAEP blocks of exactly
+    /// `PublicKey::size` big-endian bytes each, decrypts and decodes them and returns the joined message. Fails
+    /// with `CryptoError::MalformedCiphertext` if the length is not a positive multiple of the block size, and with
+    /// `CryptoError::DecryptionFailed` if any block does not decode.
+    ///
+    /// Each block is encrypted on its own and nothing binds the blocks together, so blocks that were reordered,
+    /// dropped or duplicated on the way still decrypt, to a different message. Messages that do not fit in one
+    /// block should be sent with `seal` instead, whose authenticated encryption covers the whole message.
+    pub fn decrypt_bytes(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let k = self.public_key().size();
+        if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(k) {
+            return Err(CryptoError::MalformedCiphertext);
+        }
+
+        let blocks: Vec<BigInt> = ciphertext.chunks(k).map(bytes_to_number).collect();
+        self.decrypt_blocks(&blocks, |em, k| oaep_decode(em, b"", k))
+    }
+
+    /// given a text envelope produced by `encrypt_envelope`, decodes the base64url and decrypts the message with
+    /// `decrypt_bytes`. Fails with `CryptoError::MalformedCiphertext` if the envelope is not base64url, and with
+    /// `CryptoError::InvalidUtf8` if the message is not valid UTF-8.
+    pub fn decrypt_envelope(&self, envelope: &str) -> Result<String, CryptoError> {
+        let ciphertext = URL_SAFE_NO_PAD
+            .decode(envelope.trim())
+            .map_err(|_| CryptoError::MalformedCiphertext)?;
+        let message = self.decrypt_bytes(&ciphertext)?;
+        utf8(message)
+    }
+}
+
+impl Keypair {
+    /// builds a keypair from two primes and a public exponent. Fails if e is not invertible modulo
+    /// lcm(p - 1, q - 1), or if p and q are equal.
+    fn from_primes(p: &BigInt, q: &BigInt, e: &BigInt) -> Result<Keypair, CryptoError> {
+        let n_num = p * q;
+
+        let p_minus_one = p - &*ONE;
+        let q_minus_one = q - &*ONE;
+
+        let phi_num = lcm(&p_minus_one, &q_minus_one);
+
+        let mut d_num = mod_inverse(e, &phi_num).ok_or(CryptoError::InvalidExponent)?;
+
+        if d_num < *ZERO {
+            d_num += &phi_num;
+        }
+
+        let mut qinv_num = mod_inverse(q, p).ok_or(CryptoError::InvalidKey)?;
+
+        if qinv_num < *ZERO {
+            qinv_num += p;
+        }
+
+        Ok(Keypair {
+            dp: &d_num % &p_minus_one,
+            dq: &d_num % &q_minus_one,
+            d: d_num,
+            e: e.clone(),
+            n: n_num,
+            p: p.clone(),
+            q: q.clone(),
+            qinv: qinv_num,
+            blinding: Blinding::default(),
+        })
+    }
+
+    /// mixes both seeds of a freshly generated keypair into its blinding generator.
+    fn seed_blinding(&self, seed_one: &[u8], seed_two: &[u8]) -> Result<(), CryptoError> {
+        self.add_blinding_entropy(seed_one)?;
+        self.add_blinding_entropy(seed_two)
+    }
+
+    /// returns the public half of this keypair.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::new(self.e.clone(), self.n.clone())
+    }
+
+    /// decrypts ciphertext blocks, removes the padding from each with decode and returns the joined message.
+    fn decrypt_blocks<F>(&self, blocks: &[BigInt], decode: F) -> Result<Vec<u8>, CryptoError>
+    where
+        F: Fn(&[u8], usize) -> Option<Vec<u8>>,
+    {
+        let k = self.public_key().size();
+        let mut message = Vec::new();
+
+        for c in blocks {
+            let m = self.rsadp(c)?;
+            let em = number_to_bytes(&m, k).ok_or(CryptoError::DecryptionFailed)?;
+            message.extend(decode(&em, k).ok_or(CryptoError::DecryptionFailed)?);
+        }
+
+        Ok(message)
+    }
+
+    /// the state of the blinding generator before the first seed is mixed in, from a hash of the private key.
+    fn blinding_rng(&self) -> StdRng {
+        let mut seed = Sha256::new();
+        seed.update(b"rsa blinding");
+        seed.update(self.d.to_bytes_be().1);
+        seed.update(self.n.to_bytes_be().1);
+        SeedableRng::from_seed(seed.finalize().into())
     }
 
-    /// given a ciphertext, attempts to decrypt based on the private key and modulo from this keypair. Performs
-    /// simple decryption based on RSA algorithm.
-    pub fn decrypt(&self, ciphertext: &str) -> String {
-        let private_key = string_to_number(&self.d);
-        let modulus = string_to_number(&self.n);
+    /// a random blinding factor r, invertible modulo n, and its inverse, as elements of a ring modulo n. Fails if
+    /// no entropy was added yet, or if none is found, which only happens for broken keys.
+    fn blinding_factor<R: ModRing>(
+        &self,
+        ring: &R,
+    ) -> Result<(R::Element, R::Element), CryptoError> {
+        let mut rng = self.blinding.0.borrow_mut();
+        let rng = rng.as_mut().ok_or(CryptoError::EntropyRequired)?;
+        let mut bytes = vec![0; self.public_key().size()];
 
-        let mut decrypted_values: Vec<char> = Vec::new();
+        for _ in 0..100 {
+            rng.fill(&mut bytes[..]);
+            let r = ring.to_element(&bytes_to_number(&bytes));
 
-        for c in ciphertext.split(',') {
-            let to_decrypt = string_to_number(&c.to_string());
-            let decrypted = to_decrypt.modpow(&private_key, &modulus);
-            let decrypted_u8 = decrypted.to_u8();
-            if let Some(d_u8) = decrypted_u8 {
-                decrypted_values.push(d_u8 as char)
+            if let Some(r_inverse) = ring.inverse(&r) {
+                return Ok((r, r_inverse));
             }
         }
 
-        decrypted_values.iter().collect()
+        Err(CryptoError::InvalidKey)
+    }
+
+    /// the RSA decryption primitive, c^d mod n. Fails if c is out of range.
+    ///
+    /// The exponentiation is split into two half-size ones modulo p and q and recombined with the Chinese
+    /// Remainder Theorem, which is about four times faster than a single one modulo n. Both run in constant time
+    /// (see `constant_time::modpow`), and c is blinded first: it is multiplied by r^e for a random r, which is
+    /// divided out of the result again, so the numbers that are exponentiated are unrelated to c. A fault during
+    /// the computation would leak a factor of n (see Boneh, DeMillo and Lipton), so the result is encrypted again
+    /// and `CryptoError::FaultDetected` is returned if that does not give back c.
+    ///
+    /// ## Reference
+    /// See RSADP in [RFC 8017, section 5.1.2](https://tools.ietf.org/html/rfc8017#section-5.1.2), and Kocher,
+    /// [Timing Attacks on Implementations of Diffie-Hellman, RSA, DSS, and Other
+    /// Systems](https://doi.org/10.1007/3-540-68697-5_9), section 10, for blinding.
+    pub(crate) fn rsadp(&self, c: &BigInt) -> Result<BigInt, CryptoError> {
+        if *c < *ZERO || *c >= self.n {
+            return Err(CryptoError::MalformedCiphertext);
+        }
+
+        // One context modulo n serves both the blinding and the check at the end.
+        let ring = MontgomeryContext::new(&self.n).ok_or(CryptoError::InvalidKey)?;
+        let (r, r_inverse) = self.blinding_factor(&ring)?;
+        let blinded = ring.to_number(&ring.mul(&ring.to_element(c), &ring.pow(&r, &self.e)));
+
+        let m_one =
+            constant_time::modpow(&blinded, &self.dp, &self.p).ok_or(CryptoError::InvalidKey)?;
+        let m_two =
+            constant_time::modpow(&blinded, &self.dq, &self.q).ok_or(CryptoError::InvalidKey)?;
+
+        // h = qInv * (m_1 - m_2) mod p, kept non-negative.
+        let mut h = (&self.qinv * (m_one - &m_two)) % &self.p;
+        if h < *ZERO {
+            h += &self.p;
+        }
+
+        let m = ring.to_number(&ring.mul(&ring.to_element(&(m_two + h * &self.q)), &r_inverse));
+
+        if ring.modpow(&m, &self.e) != *c {
+            return Err(CryptoError::FaultDetected);
+        }
+
+        Ok(m)
+    }
+}
+
+/// parses one decimal ciphertext block.
+fn parse_block(c: &str) -> Result<BigInt, CryptoError> {
+    BigInt::parse_bytes(c.trim().as_bytes(), 10).ok_or(CryptoError::MalformedCiphertext)
+}
+
+/// decodes a decrypted message as UTF-8.
+fn utf8(message: Vec<u8>) -> Result<String, CryptoError> {
+    String::from_utf8(message).map_err(|_| CryptoError::InvalidUtf8)
+}
+
+/// parses a comma separated list of decimal ciphertext blocks.
+fn parse_blocks(ciphertext: &str) -> Result<Vec<BigInt>, CryptoError> {
+    ciphertext.split(',').map(parse_block).collect()
+}
+
+#[cfg(test)]
+mod test_rsadp {
+    use super::*;
+    use crypto_math::BarrettContext;
+    use signature::verify;
+
+    #[test]
+    fn matches_plain_exponentiation() {
+        let k = test_keypair();
+        let d = &k.d;
+        let n = &k.n;
+
+        for c in &[
+            "0",
+            "1",
+            "2",
+            "123456789123456789",
+            &number_to_string(&(n - &*ONE)),
+        ] {
+            let c = string_to_number(c);
+            assert_eq!(k.rsadp(&c), Ok(c.modpow(d, n)));
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let k = test_keypair();
+
+        assert_eq!(k.rsadp(&k.n), Err(CryptoError::MalformedCiphertext));
+        assert_eq!(
+            k.rsadp(&string_to_number("-1")),
+            Err(CryptoError::MalformedCiphertext)
+        );
+    }
+
+    #[test]
+    fn detects_faults() {
+        let mut k = test_keypair();
+        let c = string_to_number("123456789123456789");
+
+        // A corrupted CRT exponent stands in for a fault during the computation.
+        k.dp += 2;
+        assert_eq!(k.rsadp(&c), Err(CryptoError::FaultDetected));
+    }
+
+    #[test]
+    fn blinding() {
+        let k = test_keypair();
+        let c = string_to_number("123456789123456789");
+        let m = c.modpow(&k.d, &k.n);
+
+        let barrett = BarrettContext::new(&k.n).unwrap();
+        let (r_one, _) = k.blinding_factor(&barrett).unwrap();
+        let (r_two, r_inverse) = k.blinding_factor(&barrett).unwrap();
+        assert_ne!(r_one, r_two);
+        assert_eq!(r_two * r_inverse % &k.n, *ONE);
+
+        let montgomery = MontgomeryContext::new(&k.n).unwrap();
+        let (r, r_inverse) = k.blinding_factor(&montgomery).unwrap();
+        assert_eq!(montgomery.mul(&r, &r_inverse), montgomery.one());
+
+        // Neither fresh factors nor outside randomness change the result.
+        assert_eq!(k.rsadp(&c), Ok(m.clone()));
+        assert_eq!(k.add_blinding_entropy(&[1; 32]), Ok(()));
+        assert_eq!(k.rsadp(&c), Ok(m.clone()));
+        assert_eq!(
+            k.add_blinding_entropy(&[1; 8]),
+            Err(CryptoError::SeedTooShort)
+        );
+
+        // Importing the same key with different seeds gives different factors.
+        let imported = Keypair::from_pkcs1_der(&k.to_pkcs1_der(), &[2; 32]).unwrap();
+        let again = Keypair::from_pkcs1_der(&k.to_pkcs1_der(), &[3; 32]).unwrap();
+        assert_ne!(
+            imported.blinding_factor(&barrett),
+            again.blinding_factor(&barrett)
+        );
+        assert_eq!(imported.rsadp(&c), Ok(m));
+    }
+
+    #[test]
+    fn generated_keys_have_blinding_entropy() {
+        let k = Keypair::new(&[3; 32], &[4; 32]).unwrap();
+        let c = string_to_number("123456789123456789");
+        assert_eq!(k.rsadp(&c), Ok(c.modpow(&k.d, &k.n)));
+    }
+
+    #[test]
+    fn imported_keys_decrypt_and_sign() {
+        let k = test_keypair();
+        let public_key = k.public_key().to_string();
+        let pkcs8 = k.to_pkcs8(None, &[5; 32]).unwrap();
+        let imported = [
+            Keypair::from_pkcs8(&pkcs8, None, &[6; 32]).unwrap(),
+            Keypair::from_jwk(&k.to_jwk(), &[7; 32]).unwrap(),
+        ];
+
+        for key in imported.iter() {
+            let c = encrypt_envelope("hello", &public_key, &[8; 32]).unwrap();
+            assert_eq!(key.decrypt_envelope(&c), Ok("hello".to_string()));
+
+            let signature = key.sign("hello", &[9; 32]).unwrap();
+            assert_eq!(verify("hello", &signature, &public_key), Ok(true));
+        }
+    }
+
+    #[test]
+    fn generated_components() {
+        let p = string_to_number("61");
+        let q = string_to_number("53");
+        let k = Keypair::from_primes(&p, &q, &string_to_number("17")).unwrap();
+
+        assert_eq!(k.n, string_to_number("3233"));
+        assert_eq!(k.d, string_to_number("413"));
+        assert_eq!(k.dp, string_to_number("53"));
+        assert_eq!(k.dq, string_to_number("49"));
+        assert_eq!(k.qinv, string_to_number("38"));
+    }
+
+    #[test]
+    fn equal_primes() {
+        let p = string_to_number("61");
+        let k = Keypair::from_primes(&p, &p, &string_to_number("17"));
+        assert_eq!(k.err(), Some(CryptoError::InvalidKey));
+    }
+}
+
+/// configures and generates RSA keypairs with a chosen modulus size and public exponent.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct KeypairBuilder {
+    /// Size of the modulo in bits, one of `SUPPORTED_MODULUS_BITS`
+    modulus_bits: usize,
+    /// Public exponent as a decimal string
+    public_exponent: String,
+    /// How many candidates to try per prime before giving up, or None for `PRIME_TRIES_PER_BIT` per bit
+    prime_tries: Option<usize>,
+    /// How candidates are tested for primality
+    primality_test: PrimalityTest,
+    /// The primes are composite with probability at most about 2^-security_bits
+    security_bits: u32,
+}
+
+impl Default for KeypairBuilder {
+    fn default() -> KeypairBuilder {
+        KeypairBuilder {
+            modulus_bits: 2048,
+            public_exponent: "65537".to_string(),
+            prime_tries: None,
+            primality_test: PrimalityTest::default(),
+            security_bits: DEFAULT_SECURITY_BITS,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl KeypairBuilder {
+    /// creates a builder for 2048-bit keys with e = 65537, 10 tries per bit of each prime and the Miller-Rabin test
+    /// at a security level of 128 bits.
+    pub fn new() -> KeypairBuilder {
+        KeypairBuilder::default()
+    }
+
+    /// sets the size of the modulo in bits (1024, 2048, 3072 or 4096).
+    pub fn modulus_bits(mut self, bits: usize) -> KeypairBuilder {
+        self.modulus_bits = bits;
+        self
+    }
+
+    /// sets the public exponent, given as a decimal string. It must be odd and at least 3.
+    pub fn public_exponent(mut self, e: &str) -> KeypairBuilder {
+        self.public_exponent = e.to_string();
+        self
+    }
+
+    /// sets how many random candidates are tried for each prime before giving up. By default this grows with the
+    /// size of the primes (see `PRIME_TRIES_PER_BIT`), as larger primes are further apart.
+    pub fn prime_tries(mut self, tries: usize) -> KeypairBuilder {
+        self.prime_tries = Some(tries);
+        self
+    }
+
+    /// sets the test that candidates for the primes must pass. `PrimalityTest::BailliePsw` is faster for large
+    /// keys and does not depend on the seeds.
+    pub fn primality_test(mut self, test: PrimalityTest) -> KeypairBuilder {
+        self.primality_test = test;
+        self
+    }
+
+    /// sets the security level of the Miller-Rabin test: each prime is composite with probability at most about
+    /// 2^-bits (see `generate_rsa_prime`). The number of rounds follows from this, the size of the primes and the
+    /// number of tries, so larger keys need fewer.
+    pub fn security_level(mut self, bits: u32) -> KeypairBuilder {
+        self.security_bits = bits;
+        self
+    }
+
+    /// randomly generates a new keypair based on two seeds. Fails if the settings are invalid or no suitable
+    /// prime was found within the retry budget.
+    pub fn build(&self, seed_one: &[u8], seed_two: &[u8]) -> Result<Keypair, CryptoError> {
+        if !SUPPORTED_MODULUS_BITS.contains(&self.modulus_bits) {
+            return Err(CryptoError::UnsupportedModulusSize);
+        }
+
+        let e_num = BigInt::parse_bytes(self.public_exponent.as_bytes(), 10)
+            .ok_or(CryptoError::InvalidExponent)?;
+        if e_num < string_to_number("3") || &e_num % &*TWO == *ZERO {
+            return Err(CryptoError::InvalidExponent);
+        }
+
+        let prime_bits = self.modulus_bits / 2;
+        let tries = self.prime_tries.unwrap_or(PRIME_TRIES_PER_BIT * prime_bits);
+        let q_num = generate_rsa_prime(
+            prime_bits,
+            tries,
+            &e_num,
+            self.primality_test,
+            self.security_bits,
+            seed_one,
+        )?;
+        let p_num = generate_rsa_prime(
+            prime_bits,
+            tries,
+            &e_num,
+            self.primality_test,
+            self.security_bits,
+            seed_two,
+        )?;
+
+        let keypair = Keypair::from_primes(&p_num, &q_num, &e_num)?;
+        keypair.validate()?;
+        keypair.seed_blinding(seed_one, seed_two)?;
+        Ok(keypair)
     }
 }
 
@@ -100,12 +760,12 @@ mod test_generate_key {
         ];
 
         // Generate a keypair
-        let k = Keypair::new(seed_one, seed_two);
+        let k = Keypair::new(seed_one, seed_two).unwrap();
 
         // Capture all the variables for encryption and decryption
         let e = string_to_number("65537");
-        let d = string_to_number(&k.d);
-        let n = string_to_number(&k.n);
+        let d = k.d.clone();
+        let n = k.n.clone();
 
         // Message and ciphertext
         let plaintext = string_to_number("72");
@@ -114,31 +774,45 @@ mod test_generate_key {
         let decrypted = ciphertext.modpow(&d, &n);
         assert_eq!(plaintext, decrypted);
     }
+
+    #[test]
+    fn same_seed_twice() {
+        let seed = &[7; 32];
+        assert_eq!(
+            Keypair::new(seed, seed).err(),
+            Some(CryptoError::InvalidKey)
+        );
+    }
 }
 
-/// given a public key (e, n), encrypts message m for this public key using RSA.
-#[wasm_bindgen]
-pub fn encrypt(m: &str, n: &str) -> String {
-    // receive n as hex and convert back to decimal
-    let modulus = BigInt::parse_bytes(n.as_bytes(), 32).unwrap();
-    let public_key = string_to_number("65537");
+/// given a public key (e, n), encrypts message m for this public key using RSA. The public key is expected in the
+/// format output by `Keypair::public_key_display_wasm`.
+///
+/// The output is the legacy format: a leading comma, then one decimal block per byte of m, joined by commas. It is
+/// only for talking to older clients; new code should use `encrypt_envelope` or `encrypt_bytes`. Exported to
+/// JavaScript as `encrypt`, the name existing clients call.
+#[wasm_bindgen(js_name = encrypt)]
+pub fn encrypt_legacy(m: &str, public_key: &str) -> Result<String, CryptoError> {
+    // receive n and e as radix 32 and convert back to numbers
+    let key = PublicKey::parse(public_key)?;
 
     let mut encrypted_values = String::default();
 
     for c in m.bytes() {
         let c_str = c.to_string();
         let to_encrypt = string_to_number(&c_str);
-        let encrypted = to_encrypt.modpow(&public_key, &modulus);
+        let encrypted = to_encrypt.modpow(key.e(), key.n());
 
         encrypted_values = format!("{},{}", encrypted_values, number_to_string(&encrypted));
     }
 
-    encrypted_values
+    Ok(encrypted_values)
 }
 
 #[cfg(test)]
 mod test_encrypt_decrypt {
     use super::*;
+    use crypto_math::baillie_psw;
 
     #[test]
     fn complete_encrypt_and_decrypt() {
@@ -153,15 +827,593 @@ mod test_encrypt_decrypt {
         ];
 
         // Generate a keypair
-        let k = Keypair::new(seed_one, seed_two);
+        let k = Keypair::new(seed_one, seed_two).unwrap();
 
         // Message and ciphertext
         let plaintext = "Hello World!";
-        let modulus = k.public_key_display_wasm();
-        
-        let ciphertext = encrypt(plaintext, &modulus);
-        let decrypted = k.decrypt(&ciphertext[1..]);
+        let public_key = k.public_key_display_wasm();
+
+        let ciphertext = encrypt_legacy(plaintext, &public_key).unwrap();
+        let decrypted = k.decrypt_legacy(&ciphertext[1..]).unwrap();
+
+        assert_eq!(plaintext, decrypted);
+    }
+
+    #[test]
+    fn builder_with_custom_exponent() {
+        let seed_one = &[
+            10, 16, 51, 42, 123, 31, 212, 31, 233, 15, 9, 7, 41, 32, 4, 3, 144, 122, 1, 35, 1, 13,
+            55, 23, 1, 33, 1, 1, 1, 1, 2, 1,
+        ];
+        let seed_two = &[
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+            1, 1, 1,
+        ];
+
+        let k = KeypairBuilder::new()
+            .modulus_bits(1024)
+            .public_exponent("3")
+            .build(seed_one, seed_two)
+            .unwrap();
+
+        assert_eq!(k.public_key().n().bits(), 1024);
+        assert_eq!(k.public_key().e(), &string_to_number("3"));
+
+        let plaintext = "Hello World!";
+        let ciphertext = encrypt_legacy(plaintext, &k.public_key_display_wasm()).unwrap();
+        let decrypted = k.decrypt_legacy(&ciphertext[1..]).unwrap();
 
         assert_eq!(plaintext, decrypted);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn builder_with_baillie_psw() {
+        let builder = KeypairBuilder::new().modulus_bits(1024);
+        let k = builder
+            .clone()
+            .primality_test(PrimalityTest::BailliePsw)
+            .build(&[5; 32], &[6; 32])
+            .unwrap();
+
+        // Both tests accept the same candidates, so the seeds give the same key.
+        assert_eq!(k.n, builder.build(&[5; 32], &[6; 32]).unwrap().n);
+        assert!(baillie_psw(&k.p) && baillie_psw(&k.q));
+    }
+
+    #[test]
+    fn builder_with_security_level() {
+        let builder = KeypairBuilder::new().modulus_bits(1024);
+        let k = builder
+            .clone()
+            .security_level(80)
+            .build(&[5; 32], &[6; 32])
+            .unwrap();
+
+        assert_eq!(k.n, builder.build(&[5; 32], &[6; 32]).unwrap().n);
+        assert!(baillie_psw(&k.p) && baillie_psw(&k.q));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        let seed = &[1; 32];
+
+        let error = |builder: KeypairBuilder| builder.build(seed, seed).err();
+
+        assert_eq!(
+            error(KeypairBuilder::new().modulus_bits(512)),
+            Some(CryptoError::UnsupportedModulusSize)
+        );
+        assert_eq!(
+            error(KeypairBuilder::new().public_exponent("65536")),
+            Some(CryptoError::InvalidExponent)
+        );
+        assert_eq!(
+            error(KeypairBuilder::new().public_exponent("1")),
+            Some(CryptoError::InvalidExponent)
+        );
+        assert_eq!(
+            error(KeypairBuilder::new().public_exponent("abc")),
+            Some(CryptoError::InvalidExponent)
+        );
+        assert_eq!(
+            error(KeypairBuilder::new().prime_tries(0)),
+            Some(CryptoError::PrimeNotFound)
+        );
+        assert_eq!(
+            KeypairBuilder::new().build(&[1; 8], seed).err(),
+            Some(CryptoError::SeedTooShort)
+        );
+    }
+
+    #[test]
+    fn multilingual_messages() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        for plaintext in MULTILINGUAL_MESSAGES {
+            let ciphertext = encrypt_legacy(plaintext, &public_key).unwrap();
+            assert_eq!(ciphertext.split(',').count(), plaintext.len() + 1);
+            assert_eq!(
+                k.decrypt_legacy(&ciphertext[1..]),
+                Ok(plaintext.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn reports_invalid_blocks() {
+        let k = test_keypair();
+        let encrypt_number = |m: &str| number_to_string(&string_to_number(m).modpow(&k.e, &k.n));
+
+        // 0xc3 starts a two byte sequence, but "(" cannot continue it.
+        let truncated = format!("{},{}", encrypt_number("195"), encrypt_number("40"));
+        assert_eq!(k.decrypt_legacy(&truncated), Err(CryptoError::InvalidUtf8));
+
+        // Blocks used to be skipped when they did not decrypt to a single byte.
+        let too_large = format!("{},{}", encrypt_number("72"), encrypt_number("300"));
+        assert_eq!(
+            k.decrypt_legacy(&too_large),
+            Err(CryptoError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn errors_instead_of_panics() {
+        assert_eq!(
+            Keypair::new(&[1; 31], &[2; 32]).err(),
+            Some(CryptoError::SeedTooShort)
+        );
+        assert_eq!(
+            encrypt_legacy("hi", "not a key!"),
+            Err(CryptoError::MalformedKey)
+        );
+
+        let k = test_keypair();
+        assert_eq!(
+            k.decrypt_legacy("12,abc"),
+            Err(CryptoError::MalformedCiphertext)
+        );
+    }
+}
+
+/// given a public key, encrypts message m with RSAES-OAEP (SHA-256 and MGF1). Unlike `encrypt_legacy`, identical
+/// messages give different ciphertexts, and letters are not encrypted one at a time. Messages too long for one block
+/// are split into chunks; the encrypted blocks are joined by commas. The seed drives the random padding. The public
+/// key may be in any format `PublicKey::parse` reads, including someone's `~/.ssh/id_rsa.pub`. Fails if the public
+/// key cannot be parsed or is too small for OAEP (under 528 bits).
+///
+/// ## Reference
+/// See [RFC 8017, section 7.1](https://tools.ietf.org/html/rfc8017#section-7.1).
+#[wasm_bindgen]
+pub fn encrypt_oaep(m: &str, public_key: &str, seed: &[u8]) -> Result<String, CryptoError> {
+    let key = PublicKey::parse(public_key)?;
+    let k = key.size();
+    let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+    let blocks = encrypt_blocks(m.as_bytes(), &key, oaep_max_message_len(k), |chunk| {
+        let mut oaep_seed = [0; HASH_LEN];
+        rng.fill(&mut oaep_seed);
+        oaep_encode(chunk, b"", k, &oaep_seed)
+    })?;
+
+    Ok(join_blocks(&blocks))
+}
+
+impl PublicKey {
+    /// encrypts message m with RSAES-OAEP like `encrypt_oaep`, but returns the blocks as raw bytes: each is
+    /// exactly `size()` bytes, big-endian, so the ciphertext needs no separators. The seed drives the random
+    /// padding. Fails if the key is too small for OAEP.
+    pub fn encrypt_bytes(&self, m: &[u8], seed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let k = self.size();
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+        let blocks = encrypt_blocks(m, self, oaep_max_message_len(k), |chunk| {
+            let mut oaep_seed = [0; HASH_LEN];
+            rng.fill(&mut oaep_seed);
+            oaep_encode(chunk, b"", k, &oaep_seed)
+        })?;
+
+        let mut ciphertext = Vec::with_capacity(blocks.len() * k);
+        for c in &blocks {
+            ciphertext.extend(number_to_bytes(c, k).ok_or(CryptoError::MalformedCiphertext)?);
+        }
+
+        Ok(ciphertext)
+    }
+}
+
+/// given a public key in any format `PublicKey::parse` reads, encrypts the bytes m with RSAES-OAEP into
+/// fixed-width blocks (see `PublicKey::encrypt_bytes`). In JavaScript both m and the result are `Uint8Array`s.
+#[wasm_bindgen]
+pub fn encrypt_bytes(m: &[u8], public_key: &str, seed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    PublicKey::parse(public_key)?.encrypt_bytes(m, seed)
+}
+
+/// given a public key in any format `PublicKey::parse` reads, encrypts message m with `encrypt_bytes` and encodes
+/// the result as unpadded base64url, a compact envelope that can be sent over the chat socket as text.
+///
+/// ## Reference
+/// See [RFC 4648, section 5](https://tools.ietf.org/html/rfc4648#section-5).
+#[wasm_bindgen]
+pub fn encrypt_envelope(m: &str, public_key: &str, seed: &[u8]) -> Result<String, CryptoError> {
+    Ok(URL_SAFE_NO_PAD.encode(encrypt_bytes(m.as_bytes(), public_key, seed)?))
+}
+
+#[cfg(test)]
+mod test_encrypt_bytes {
+    use super::*;
+    use crypto_math::test_seed;
+
+    #[test]
+    fn fixed_width_blocks() {
+        let k = test_keypair();
+        let key = k.public_key();
+        let m = [0xffu8; 100];
+
+        // 100 bytes need two OAEP blocks with a 1024-bit key, each exactly 128 bytes.
+        let ciphertext = key.encrypt_bytes(&m, test_seed()).unwrap();
+        assert_eq!(ciphertext.len(), 2 * key.size());
+        assert_eq!(k.decrypt_bytes(&ciphertext), Ok(m.to_vec()));
+
+        let empty = key.encrypt_bytes(&[], test_seed()).unwrap();
+        assert_eq!(empty.len(), key.size());
+        assert_eq!(k.decrypt_bytes(&empty), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn matches_decimal_blocks() {
+        let k = test_keypair();
+        let ciphertext =
+            encrypt_bytes(b"Hello World!", &k.public_key_display_wasm(), test_seed()).unwrap();
+        let decimal = number_to_string(&bytes_to_number(&ciphertext));
+
+        assert_eq!(k.decrypt_oaep(&decimal), Ok("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn envelope_round_trip() {
+        let k = test_keypair();
+        let public_key = k.public_key_pem();
+
+        let envelope = encrypt_envelope("Hello World!", &public_key, test_seed()).unwrap();
+        assert!(envelope
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(envelope.len(), 171);
+        assert!(
+            envelope.len()
+                < encrypt_oaep("Hello World!", &public_key, test_seed())
+                    .unwrap()
+                    .len()
+        );
+
+        assert_eq!(
+            k.decrypt_envelope(&envelope),
+            Ok("Hello World!".to_string())
+        );
+    }
+
+    #[test]
+    fn multilingual_envelopes() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        for plaintext in MULTILINGUAL_MESSAGES {
+            let envelope = encrypt_envelope(plaintext, &public_key, test_seed()).unwrap();
+            assert_eq!(k.decrypt_envelope(&envelope), Ok(plaintext.to_string()));
+        }
+
+        // Bytes that are not UTF-8 can be sent, but not read back as text.
+        let ciphertext = k
+            .public_key()
+            .encrypt_bytes(&[0xc3, 0x28], test_seed())
+            .unwrap();
+        let envelope = URL_SAFE_NO_PAD.encode(&ciphertext);
+        assert_eq!(k.decrypt_bytes(&ciphertext), Ok(vec![0xc3, 0x28]));
+        assert_eq!(k.decrypt_envelope(&envelope), Err(CryptoError::InvalidUtf8));
+    }
+
+    #[test]
+    fn blocks_are_not_bound_together() {
+        let k = test_keypair();
+        let size = k.public_key().size();
+        let m = [[b'a'; 62], [b'b'; 62]].concat();
+        let ciphertext = k.public_key().encrypt_bytes(&m, test_seed()).unwrap();
+
+        let swapped = [&ciphertext[size..], &ciphertext[..size]].concat();
+        let swapped_m = [[b'b'; 62], [b'a'; 62]].concat();
+        assert_eq!(k.decrypt_bytes(&swapped), Ok(swapped_m));
+        assert_eq!(k.decrypt_bytes(&ciphertext[..size]), Ok(vec![b'a'; 62]));
+    }
+
+    #[test]
+    fn rejects_malformed_ciphertext() {
+        let k = test_keypair();
+        let ciphertext = k.public_key().encrypt_bytes(b"hi", test_seed()).unwrap();
+
+        let malformed = Err(CryptoError::MalformedCiphertext);
+        assert_eq!(k.decrypt_bytes(&[]), malformed);
+        assert_eq!(k.decrypt_bytes(&ciphertext[1..]), malformed);
+        assert_eq!(k.decrypt_bytes(&[0xff; 128]), malformed);
+        assert_eq!(
+            k.decrypt_envelope("not base64!").err(),
+            Some(CryptoError::MalformedCiphertext)
+        );
+
+        let mut tampered = ciphertext.clone();
+        tampered[127] ^= 1;
+        assert_eq!(
+            k.decrypt_bytes(&tampered),
+            Err(CryptoError::DecryptionFailed)
+        );
+    }
+}
+
+/// joins ciphertext blocks as comma separated decimal numbers.
+fn join_blocks(blocks: &[BigInt]) -> String {
+    let blocks: Vec<String> = blocks.iter().map(number_to_string).collect();
+    blocks.join(",")
+}
+
+/// splits message m into chunks of at most max_len bytes, pads each with encode and encrypts it for the public
+/// key.
+fn encrypt_blocks<F>(
+    m: &[u8],
+    key: &PublicKey,
+    max_len: usize,
+    mut encode: F,
+) -> Result<Vec<BigInt>, CryptoError>
+where
+    F: FnMut(&[u8]) -> Option<Vec<u8>>,
+{
+    if max_len == 0 {
+        return Err(CryptoError::KeyTooSmall);
+    }
+
+    // An empty message still needs one block, so that the recipient can tell it apart from no message at all.
+    let chunks: Vec<&[u8]> = if m.is_empty() {
+        vec![&[]]
+    } else {
+        m.chunks(max_len).collect()
+    };
+
+    let mut blocks = Vec::new();
+
+    for chunk in chunks {
+        let em = encode(chunk).ok_or(CryptoError::KeyTooSmall)?;
+        blocks.push(key.rsaep(&bytes_to_number(&em))?);
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod test_oaep {
+    use super::*;
+    use crypto_math::test_seed;
+
+    #[test]
+    fn openssl_vectors() {
+        let k = test_keypair();
+
+        // Produced with `openssl pkeyutl -encrypt -pkeyopt rsa_padding_mode:oaep -pkeyopt rsa_oaep_md:sha256
+        // -pkeyopt rsa_mgf1_md:sha256` against the key from `test_keypair`.
+        let vectors = [
+            ("Hello World!", "21231147577746627467064782642118064530140652740383086228620208349690123858808938640606117256407967543803672502292704313207452427759765639897208569315111214983418267097955444188566358519053436690846275939253712468436368071181337749099701889529282986302750830272486682884470051542413768217700856964213609739174"),
+            ("", "108966391181837384592690382816847638107465613149946772273697886983490759882527531591997887425761770620358358569848834135098131195942630457149486775686520070976989843245428940984689815458197521914678728137337645660221935067705540073616105107112033180671796555130959626652415263694002783808445930748436741834419"),
+            ("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx", "69678686396442508093374394430455714805360121855162187143722948506757358748046776804541631823675624329210625224852964894499675047693008584110372187279916723142165853577522849023729690862644048876393786236430463876829285395151571742617283223689605665186277037693347410228628500181960816628866476303528165358629"),
+        ];
+
+        for (plaintext, ciphertext) in vectors.iter() {
+            assert_eq!(k.decrypt_oaep(ciphertext), Ok(plaintext.to_string()));
+        }
+    }
+
+    #[test]
+    fn complete_encrypt_and_decrypt() {
+        let k = test_keypair();
+        let plaintext =
+            "A message that is long enough to need more than one OAEP block with a 1024-bit key.";
+
+        let ciphertext =
+            encrypt_oaep(plaintext, &k.public_key_display_wasm(), test_seed()).unwrap();
+        assert_eq!(ciphertext.split(',').count(), 2);
+        assert_eq!(k.decrypt_oaep(&ciphertext), Ok(plaintext.to_string()));
+    }
+
+    #[test]
+    fn multilingual_messages() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        for plaintext in MULTILINGUAL_MESSAGES {
+            let ciphertext = encrypt_oaep(plaintext, &public_key, test_seed()).unwrap();
+            assert_eq!(k.decrypt_oaep(&ciphertext), Ok(plaintext.to_string()));
+        }
+    }
+
+    #[test]
+    fn encrypt_to_ssh_public_key() {
+        let k = test_keypair();
+        let public_key = k.public_key_openssh("alice@example");
+
+        let ciphertext = encrypt_oaep("Hello World!", &public_key, test_seed()).unwrap();
+        assert_eq!(k.decrypt_oaep(&ciphertext), Ok("Hello World!".to_string()));
+    }
+
+    #[test]
+    fn empty_message() {
+        let k = test_keypair();
+        let ciphertext = encrypt_oaep("", &k.public_key_display_wasm(), test_seed()).unwrap();
+        assert_eq!(k.decrypt_oaep(&ciphertext), Ok("".to_string()));
+    }
+
+    #[test]
+    fn hides_repeated_letters() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        let one = encrypt_oaep("a", &public_key, test_seed()).unwrap();
+        let two = encrypt_oaep("a", &public_key, &[2; 32]).unwrap();
+        assert_ne!(one, two);
+    }
+
+    #[test]
+    fn key_too_small() {
+        // 1022117 = 1009 * 1013 passes validation, but is far too small for OAEP.
+        let key = PublicKey::new(string_to_number("65537"), string_to_number("1022117"));
+        assert_eq!(
+            encrypt_oaep("a", &key.to_string(), test_seed()),
+            Err(CryptoError::KeyTooSmall)
+        );
+    }
+
+    #[test]
+    fn tampered_ciphertext() {
+        let k = test_keypair();
+        let ciphertext =
+            encrypt_oaep("Hello World!", &k.public_key_display_wasm(), test_seed()).unwrap();
+        let tampered = number_to_string(&(string_to_number(&ciphertext) + 1));
+
+        assert_eq!(
+            k.decrypt_oaep(&tampered),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            k.decrypt_oaep("not a number"),
+            Err(CryptoError::MalformedCiphertext)
+        );
+    }
+}
+
+/// given a public key, encrypts message m with RSAES-PKCS1-v1_5. Prefer `encrypt_oaep`; this scheme exists to
+/// exchange messages with legacy tooling such as `openssl rsautl`. Blocks are split and joined as in
+/// `encrypt_oaep`, and the seed drives the random padding. Fails if the public key cannot be parsed or is too
+/// small (under 96 bits).
+///
+/// ## Reference
+/// See [RFC 8017, section 7.2](https://tools.ietf.org/html/rfc8017#section-7.2).
+#[wasm_bindgen]
+pub fn encrypt_pkcs1v15(m: &str, public_key: &str, seed: &[u8]) -> Result<String, CryptoError> {
+    let key = PublicKey::parse(public_key)?;
+    let k = key.size();
+    let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+
+    let blocks = encrypt_blocks(m.as_bytes(), &key, pkcs1v15_max_message_len(k), |chunk| {
+        pkcs1v15_encode(chunk, k, &mut rng)
+    })?;
+
+    Ok(join_blocks(&blocks))
+}
+
+#[cfg(test)]
+mod test_pkcs1v15 {
+    use super::*;
+    use crypto_math::test_seed;
+
+    #[test]
+    fn openssl_vectors() {
+        let k = test_keypair();
+
+        // Produced with `openssl pkeyutl -encrypt -pkeyopt rsa_padding_mode:pkcs1` against the key from
+        // `test_keypair`.
+        let vectors = [
+            ("Hello World!", "21125804573854675442837328226462519145779280886738617084697494801193391782389519416719145285246229704687082958683879493997925524833638967330138246376134104556072202154039978638793514811402511527822584090628381328152344875734179004978891694659607595309425573857520347287360369055055607066123105227600314462015"),
+            ("", "80994121575992547986832535368180051483155509803141447872246328649443841813812965349461682901329831652298231251911165309468645939379778401238384544650498626381603696756266446578804811467838174099574943168855646620251347428796666807701915261450437279757907212043098373779370114217534256589986305911236125927075"),
+            ("yyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy", "91469721521578552388820362674745279143004675346163020421170815674546385330191577839030499547295032003648193377863142977577034141403873188887005741384782412733385539203262956027603232135320519528937355790316089455267445392341575852560255427310766440485983700873555333983422036195358080419908704000716679852188"),
+        ];
+
+        for (plaintext, ciphertext) in vectors.iter() {
+            assert_eq!(k.decrypt_pkcs1v15(ciphertext), Ok(plaintext.to_string()));
+        }
+    }
+
+    #[test]
+    fn complete_encrypt_and_decrypt() {
+        let k = test_keypair();
+        let plaintext = "A message that is long enough to need more than one PKCS #1 v1.5 block with a 1024-bit key, \
+                         which holds at most 117 bytes.";
+
+        let ciphertext =
+            encrypt_pkcs1v15(plaintext, &k.public_key_display_wasm(), test_seed()).unwrap();
+        assert_eq!(ciphertext.split(',').count(), 2);
+        assert_eq!(k.decrypt_pkcs1v15(&ciphertext), Ok(plaintext.to_string()));
+    }
+
+    #[test]
+    fn uniform_failure() {
+        let k = test_keypair();
+        let oaep = encrypt_oaep("Hello World!", &k.public_key_display_wasm(), test_seed()).unwrap();
+
+        assert_eq!(
+            k.decrypt_pkcs1v15(&oaep),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            k.decrypt_pkcs1v15("12345"),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            k.decrypt_pkcs1v15("not a number"),
+            Err(CryptoError::MalformedCiphertext)
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_fails_like_invalid_padding() {
+        let k = test_keypair();
+        let key = k.public_key();
+        let size = key.size();
+        let mut rng: StdRng = SeedableRng::from_seed(from_slice(test_seed()).unwrap());
+
+        // 0xc3 starts a two byte sequence, but "(" cannot continue it.
+        let blocks = encrypt_blocks(
+            &[0xc3, 0x28],
+            &key,
+            pkcs1v15_max_message_len(size),
+            |chunk| pkcs1v15_encode(chunk, size, &mut rng),
+        )
+        .unwrap();
+        let bad_padding = "12345";
+
+        assert_eq!(
+            k.decrypt_pkcs1v15(&join_blocks(&blocks)),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            k.decrypt_pkcs1v15(&join_blocks(&blocks)),
+            k.decrypt_pkcs1v15(bad_padding)
+        );
+    }
+}
+
+// Chat messages beyond ASCII: accents, Cyrillic, CJK, right-to-left script and emoji (including a ZWJ sequence).
+#[cfg(test)]
+pub static MULTILINGUAL_MESSAGES: &[&str] = &[
+    "Ça va? Très bien, à bientôt!",
+    "Привет, как дела?",
+    "你好，世界",
+    "こんにちは、元気ですか？",
+    "مرحبا بالعالم",
+    "See you at 8 🍕🎉👩‍💻",
+];
+
+// A 1024-bit key generated with `openssl genrsa 1024`, for checking our padding schemes against OpenSSL output. It
+// comes with blinding entropy, like a generated key.
+#[cfg(test)]
+pub fn test_keypair() -> Keypair {
+    let keypair = Keypair {
+        d: string_to_number("89881016666204801475964345980484046496992945440784356803492586229989277732976242091992571219049873744970058746775320406568632455941848658299165436930914319297976776888557371106729689000257604164367784337174484509908026338047519810493804248143382895060609217709281650363601378052205191843256567420020928107137"),
+        e: string_to_number("65537"),
+        n: string_to_number("119570724854925789102189739820619173336989011557175308375903167094730580033819096662530796118522076478241773710770830088610110126361256404655578216144473303486453947720998046445814563132975512591924879866157323501017042987678283134684517190369815229157482329808926904089471689116254014429012042009805032570091"),
+        p: string_to_number("11573695417581481149048800363772247875607355646144102098166923700144408682727472820263777887409321830132347372401383708784607690022265971386361288093243743"),
+        q: string_to_number("10331248623778982137121321124742144440246176665475355724860606774109171266820407287032743014114217205739581293226319774281712863852957302070494938026421237"),
+        dp: string_to_number("5855632583978008635429759104963000977453784862212889472679842776590144545857722260315643791914770179341873661810657811860170923077929949177242894709794101"),
+        dq: string_to_number("9542103093610717117690628026943051178914831617832503126196459841671151348885455139713123386596085900798367262161563120940024129299225757230711340062518941"),
+        qinv: string_to_number("6770483034774692892772353955387529187855116325584057562480805509187616007942565066373214094887617328622226431163333607488705801058577658823835577963254673"),
+        blinding: Blinding::default(),
+    };
+
+    keypair.add_blinding_entropy(&[0x42; 32]).unwrap();
+    keypair
+}
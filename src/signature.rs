@@ -0,0 +1,221 @@
+use crypto_math::{bytes_to_number, from_slice, number_to_bytes, number_to_string};
+use error::CryptoError;
+use num::bigint::BigInt;
+use padding::{pkcs1v15_signature_encode, pss_encode, pss_verify, HASH_LEN};
+use rand::{Rng, SeedableRng, StdRng};
+use rsa::{Keypair, PublicKey};
+use wasm_bindgen::prelude::*;
+
+/// the padding schemes available for RSA signatures.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureScheme {
+    /// RSASSA-PSS with SHA-256, MGF1 and a 32 byte salt. This is the default.
+    Pss,
+    /// RSASSA-PKCS1-v1_5 with SHA-256, for tools that do not understand PSS.
+    Pkcs1v15,
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// signs a message with RSASSA-PSS so that others can check it was sent by the owner of this keypair. The
+    /// seed drives the random salt. Returns the signature as a decimal string. Fails if the key is too small for
+    /// PSS (under 528 bits).
+    pub fn sign(&self, message: &str, seed: &[u8]) -> Result<String, CryptoError> {
+        self.sign_with(message, seed, SignatureScheme::Pss)
+    }
+
+    /// signs a message with the chosen signature scheme. The seed is ignored by RSASSA-PKCS1-v1_5, which is
+    /// deterministic.
+    pub fn sign_with(
+        &self,
+        message: &str,
+        seed: &[u8],
+        scheme: SignatureScheme,
+    ) -> Result<String, CryptoError> {
+        let key = self.public_key();
+        let mod_bits = key.n().bits();
+
+        let em = match scheme {
+            SignatureScheme::Pss => {
+                let mut rng: StdRng = SeedableRng::from_seed(from_slice(seed)?);
+                let mut salt = [0; HASH_LEN];
+                rng.fill(&mut salt);
+                pss_encode(message.as_bytes(), mod_bits - 1, &salt)
+            }
+            SignatureScheme::Pkcs1v15 => pkcs1v15_signature_encode(message.as_bytes(), key.size()),
+        }
+        .ok_or(CryptoError::KeyTooSmall)?;
+
+        let signature = self.rsadp(&bytes_to_number(&em))?;
+        Ok(number_to_string(&signature))
+    }
+}
+
+/// checks a RSASSA-PSS signature made by `Keypair::sign` against a message and the signer's public key (in the
+/// format output by `Keypair::public_key_display_wasm`). Returns false for forged or malformed signatures, and
+/// fails if the public key cannot be parsed.
+///
+/// ## Reference
+/// See [RFC 8017, section 8.1](https://tools.ietf.org/html/rfc8017#section-8.1).
+#[wasm_bindgen]
+pub fn verify(message: &str, signature: &str, public_key: &str) -> Result<bool, CryptoError> {
+    verify_with(message, signature, public_key, SignatureScheme::Pss)
+}
+
+/// checks a signature made with the chosen signature scheme.
+///
+/// ## Reference
+/// See [RFC 8017, section 8.2](https://tools.ietf.org/html/rfc8017#section-8.2) for RSASSA-PKCS1-v1_5.
+#[wasm_bindgen]
+pub fn verify_with(
+    message: &str,
+    signature: &str,
+    public_key: &str,
+    scheme: SignatureScheme,
+) -> Result<bool, CryptoError> {
+    let key = PublicKey::parse(public_key)?;
+
+    let valid = BigInt::parse_bytes(signature.trim().as_bytes(), 10)
+        .and_then(|s| key.rsaep(&s).ok())
+        .map(|m| verify_encoded(message.as_bytes(), &m, &key, scheme))
+        .unwrap_or(false);
+
+    Ok(valid)
+}
+
+/// checks the encoded message m recovered from a signature.
+fn verify_encoded(message: &[u8], m: &BigInt, key: &PublicKey, scheme: SignatureScheme) -> bool {
+    match scheme {
+        SignatureScheme::Pss => {
+            let em_bits = key.n().bits() - 1;
+            number_to_bytes(m, em_bits.div_ceil(8))
+                .map(|em| pss_verify(message, &em, em_bits, HASH_LEN))
+                .unwrap_or(false)
+        }
+        SignatureScheme::Pkcs1v15 => {
+            let expected = pkcs1v15_signature_encode(message, key.size());
+            expected.is_some() && number_to_bytes(m, key.size()) == expected
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sign_and_verify {
+    use super::*;
+    use crypto_math::test_seed;
+    use rsa::test_keypair;
+
+    #[test]
+    fn pss_round_trip() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        let signature = k.sign("Hello World!", test_seed()).unwrap();
+        assert!(verify("Hello World!", &signature, &public_key).unwrap());
+        assert!(!verify("Hello World?", &signature, &public_key).unwrap());
+    }
+
+    #[test]
+    fn pss_is_randomized() {
+        let k = test_keypair();
+        let one = k.sign("Hello World!", test_seed()).unwrap();
+        let two = k.sign("Hello World!", &[2; 32]).unwrap();
+        assert_ne!(one, two);
+    }
+
+    #[test]
+    fn pkcs1v15_round_trip() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        let signature = k
+            .sign_with("Hello World!", test_seed(), SignatureScheme::Pkcs1v15)
+            .unwrap();
+        assert!(verify_with(
+            "Hello World!",
+            &signature,
+            &public_key,
+            SignatureScheme::Pkcs1v15
+        )
+        .unwrap());
+        assert!(!verify_with(
+            "Hello World?",
+            &signature,
+            &public_key,
+            SignatureScheme::Pkcs1v15
+        )
+        .unwrap());
+        assert!(!verify("Hello World!", &signature, &public_key).unwrap());
+    }
+
+    #[test]
+    fn openssl_vectors() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        // Produced with `openssl dgst -sha256 -sign` against the key from `test_keypair`.
+        let pkcs1v15 = "110485443415736072853204783242041596274758234693896140480921646341491706274909803437693334721084921868822831441573941427309920644758283353139787659563394426876301552421861444595944983958051882814500607098028308773050201561803822622327012385126505654338371246122855741714041089615208842595495584869768872784634";
+        assert!(verify_with(
+            "Hello World!",
+            pkcs1v15,
+            &public_key,
+            SignatureScheme::Pkcs1v15
+        )
+        .unwrap());
+
+        // PKCS #1 v1.5 signatures are deterministic, so ours must match OpenSSL's exactly.
+        let ours = k.sign_with("Hello World!", test_seed(), SignatureScheme::Pkcs1v15);
+        assert_eq!(ours, Ok(pkcs1v15.to_string()));
+
+        // Produced with `openssl dgst -sha256 -sigopt rsa_padding_mode:pss -sigopt rsa_pss_saltlen:32 -sign`.
+        let pss = "96220668410786541692210058980854480146463898490811083408683396848486080798869856579225883654627740655257081511337934938768013917889568767144761698700322677207194025955330178308124938346000427001439033708763637328052234161810616411944038709148255223987342570158004781016973033789666348841133438065424185720980";
+        assert!(verify("Hello World!", pss, &public_key).unwrap());
+    }
+
+    #[test]
+    fn forged_sender() {
+        let k = test_keypair();
+        let signature = k.sign("Hello World!", test_seed()).unwrap();
+
+        let other = PublicKey::new(
+            k.public_key().e() + BigInt::from(2),
+            k.public_key().n().clone(),
+        );
+        assert!(!verify("Hello World!", &signature, &other.to_string()).unwrap());
+    }
+
+    #[test]
+    fn malformed_input() {
+        let k = test_keypair();
+        let public_key = k.public_key_display_wasm();
+
+        assert_eq!(
+            verify("Hello World!", "not a number", &public_key),
+            Ok(false)
+        );
+        assert_eq!(verify("Hello World!", "-1", &public_key), Ok(false));
+        assert_eq!(
+            verify("Hello World!", "12345", "not a key!"),
+            Err(CryptoError::MalformedKey)
+        );
+    }
+
+    #[test]
+    fn key_too_small() {
+        let seed_one = &[
+            10, 16, 51, 42, 123, 31, 212, 31, 233, 15, 9, 7, 41, 32, 4, 3, 144, 122, 1, 35, 1, 13,
+            55, 23, 1, 33, 1, 1, 1, 1, 2, 1,
+        ];
+        let seed_two = &[1; 32];
+        let k = Keypair::new(seed_one, seed_two).unwrap();
+
+        assert_eq!(
+            k.sign("Hello World!", test_seed()),
+            Err(CryptoError::KeyTooSmall)
+        );
+        assert!(k
+            .sign_with("Hello World!", test_seed(), SignatureScheme::Pkcs1v15)
+            .is_ok());
+    }
+}
@@ -0,0 +1,123 @@
+use der::{encode, encode_sequence, Reader, BIT_STRING, NULL, OBJECT_IDENTIFIER, RSA_ENCRYPTION};
+use error::CryptoError;
+use pem;
+use rsa::{Keypair, PublicKey};
+use wasm_bindgen::prelude::*;
+
+/// PEM label of a SubjectPublicKeyInfo.
+pub static PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+
+impl PublicKey {
+    /// encodes this key as an X.509 SubjectPublicKeyInfo, the format most tools expect for a "public key" (for
+    /// example `openssl rsa -pubin`).
+    ///
+    /// ## Reference
+    /// See [RFC 5280, section 4.1](https://tools.ietf.org/html/rfc5280#section-4.1) and
+    /// [RFC 3279, section 2.3.1](https://tools.ietf.org/html/rfc3279#section-2.3.1).
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        let mut subject_public_key = vec![0];
+        subject_public_key.extend(self.to_pkcs1_der());
+
+        encode_sequence(&[
+            encode_sequence(&[encode(OBJECT_IDENTIFIER, RSA_ENCRYPTION), encode(NULL, &[])]),
+            encode(BIT_STRING, &subject_public_key),
+        ])
+    }
+
+    /// decodes an X.509 SubjectPublicKeyInfo holding an RSA key. Fails for other algorithms or invalid DER.
+    pub fn from_spki_der(der: &[u8]) -> Result<PublicKey, CryptoError> {
+        let (oid, parameters, key) = Reader::new(der)
+            .finish(|reader| {
+                reader.read_sequence()?.finish(|info| {
+                    let mut algorithm = info.read_sequence()?;
+                    let oid = algorithm.read(OBJECT_IDENTIFIER)?;
+                    Some((oid, algorithm, info.read_bit_string()?))
+                })
+            })
+            .ok_or(CryptoError::MalformedKey)?;
+
+        if oid != RSA_ENCRYPTION {
+            return Err(CryptoError::UnsupportedKey);
+        }
+        parameters
+            .finish(|parameters| parameters.read_null())
+            .ok_or(CryptoError::MalformedKey)?;
+
+        PublicKey::from_pkcs1_der(key)
+    }
+
+    /// encodes this key as a PEM "PUBLIC KEY".
+    pub fn to_spki_pem(&self) -> String {
+        pem::encode(PUBLIC_KEY_LABEL, &self.to_spki_der())
+    }
+
+    /// decodes a PEM "PUBLIC KEY".
+    pub fn from_spki_pem(pem: &str) -> Result<PublicKey, CryptoError> {
+        let der = pem::decode(PUBLIC_KEY_LABEL, pem).ok_or(CryptoError::MalformedKey)?;
+        PublicKey::from_spki_der(&der)
+    }
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// outputs the public key as a PEM encoded SubjectPublicKeyInfo, which standard tools such as OpenSSL can read
+    /// and which `encrypt_legacy` accepts in place of the radix 32 format.
+    pub fn public_key_pem(&self) -> String {
+        self.public_key().to_spki_pem()
+    }
+}
+
+#[cfg(test)]
+mod test_spki {
+    use super::*;
+    use crypto_math::string_to_number;
+    use rsa::test_keypair;
+
+    // `openssl rsa -pubout` for the key from `test_keypair`.
+    static OPENSSL_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQCqRjy8CT7zpami0mX9D/Nkb1cN
+rX/IV0VxaiPFRabD1AL/HY/D4A9fDPxwBWrLvjTPbABlBDu+s07yY69ImgVnSlZ2
+GFKIS7XvDMIP9V0LyPGbxdSiVdCeCpR7tGZdyrrQy4d9yIiDVDHtA/b5kOtoSLj1
+kwFE0d7TfuMf2ty86wIDAQAB
+-----END PUBLIC KEY-----
+";
+
+    #[test]
+    fn matches_openssl() {
+        let k = test_keypair();
+        assert_eq!(k.public_key_pem(), OPENSSL_PEM);
+        assert_eq!(PublicKey::from_spki_pem(OPENSSL_PEM), Ok(k.public_key()));
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = PublicKey::new(string_to_number("3"), string_to_number("1022117"));
+        assert_eq!(PublicKey::from_spki_der(&key.to_spki_der()), Ok(key));
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let key = test_keypair().public_key();
+        let mut der = key.to_spki_der();
+
+        // Turn rsaEncryption (1.2.840.113549.1.1.1) into md2WithRSAEncryption (1.2.840.113549.1.1.2).
+        let oid_start = der
+            .windows(RSA_ENCRYPTION.len())
+            .position(|window| window == RSA_ENCRYPTION)
+            .unwrap();
+        der[oid_start + RSA_ENCRYPTION.len() - 1] = 0x02;
+        assert_eq!(
+            PublicKey::from_spki_der(&der),
+            Err(CryptoError::UnsupportedKey)
+        );
+    }
+
+    #[test]
+    fn rejects_pkcs1_pem() {
+        let key = test_keypair().public_key();
+        assert_eq!(
+            PublicKey::from_spki_pem(&key.to_pkcs1_pem()),
+            Err(CryptoError::MalformedKey)
+        );
+    }
+}
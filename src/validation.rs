@@ -0,0 +1,168 @@
+use crypto_math::{gcd, is_prime, lcm, SMALL_PRIMES};
+use error::CryptoError;
+use num::bigint::{BigInt, Sign};
+use num::pow;
+use num_traits::{One, Signed, Zero};
+use rsa::{Keypair, PublicKey};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+/// p and q must differ in more than their lowest nlen / 2 - DISTANCE_MARGIN bits, see FIPS 186-4, B.3.1.
+const DISTANCE_MARGIN: usize = 100;
+
+impl PublicKey {
+    /// checks that this key can be a valid RSA public key: n is odd and has no prime factor below 1000, and e is
+    /// odd with 3 <= e < n. Fails with `CryptoError::InvalidKey` or `CryptoError::InvalidExponent` otherwise.
+    ///
+    /// ## Reference
+    /// See the partial public-key validation in
+    /// [NIST SP 800-56B Rev. 2, section 6.4.2.2](https://doi.org/10.6028/NIST.SP.800-56Br2).
+    pub fn validate(&self) -> Result<(), CryptoError> {
+        let (n, e) = (self.n(), self.e());
+
+        if n.sign() != Sign::Plus
+            || SMALL_PRIMES
+                .iter()
+                .any(|&prime| (n % BigInt::from(prime)).is_zero())
+        {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        if *e < BigInt::from(3) || (e % BigInt::from(2)).is_zero() || e >= n {
+            return Err(CryptoError::InvalidExponent);
+        }
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Keypair {
+    /// checks that the components of this keypair belong together: the public key is valid, n = p * q for two
+    /// distinct primes that are not too close to each other, e is invertible modulo lambda(n) = lcm(p - 1, q - 1)
+    /// with d as its inverse, and the CRT values match d, p and q. Runs after generation and on every import.
+    ///
+    /// ## Reference
+    /// See [FIPS 186-4, appendix B.3.1](https://doi.org/10.6028/NIST.FIPS.186-4) and
+    /// [RFC 8017, section 3.2](https://tools.ietf.org/html/rfc8017#section-3.2).
+    pub fn validate(&self) -> Result<(), CryptoError> {
+        self.public_key().validate()?;
+
+        let (n, e, d, p, q) = (&self.n, &self.e, &self.d, &self.p, &self.q);
+        let one = BigInt::one();
+
+        if p <= &one || q <= &one || p == q || *n != p * q {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        let half_bits = n.bits() / 2;
+        let min_distance = pow(BigInt::from(2), half_bits.saturating_sub(DISTANCE_MARGIN));
+        if (p - q).abs() <= min_distance {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        // Miller-Rabin bases derived from n cannot be chosen by whoever crafted the key, as changing p or q
+        // changes them.
+        let seed = Sha256::digest(n.to_bytes_be().1);
+        if !is_prime(p, &seed)? || !is_prime(q, &seed)? {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        let p_minus_one = p - &one;
+        let q_minus_one = q - &one;
+        let lambda = lcm(&p_minus_one, &q_minus_one);
+        if !gcd(e, &lambda).is_one() {
+            return Err(CryptoError::InvalidExponent);
+        }
+
+        // FIPS 186-4 wants d < lambda(n), but keys computed modulo phi(n) are common and just as correct.
+        if *d <= pow(BigInt::from(2), half_bits) || d >= n || !((e * d) % &lambda).is_one() {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        if self.dp != d % &p_minus_one
+            || self.dq != d % &q_minus_one
+            || self.qinv.sign() != Sign::Plus
+            || self.qinv >= *p
+            || !((&self.qinv * q) % p).is_one()
+        {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_validation {
+    use super::*;
+    use crypto_math::string_to_number;
+    use rsa::test_keypair;
+
+    #[test]
+    fn accepts_good_keys() {
+        let k = test_keypair();
+        assert_eq!(k.public_key().validate(), Ok(()));
+        assert_eq!(k.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_public_keys() {
+        let n = test_keypair().public_key().n().clone();
+
+        // 3233 = 53 * 61
+        let toy = PublicKey::new(string_to_number("3"), string_to_number("3233"));
+        assert_eq!(toy.validate(), Err(CryptoError::InvalidKey));
+
+        let even = PublicKey::new(string_to_number("65537"), &n + 1);
+        assert_eq!(even.validate(), Err(CryptoError::InvalidKey));
+
+        for e in &["1", "65536", "-65537"] {
+            let key = PublicKey::new(string_to_number(e), n.clone());
+            assert_eq!(key.validate(), Err(CryptoError::InvalidExponent));
+        }
+
+        let huge_e = PublicKey::new(&n + 2, n.clone());
+        assert_eq!(huge_e.validate(), Err(CryptoError::InvalidExponent));
+    }
+
+    #[test]
+    fn rejects_inconsistent_keypairs() {
+        let corruptions: &[fn(&mut Keypair)] = &[
+            |k| k.n += 2,
+            |k| k.d += 2,
+            |k| k.dp += 2,
+            |k| k.dq += 2,
+            |k| k.qinv += 1,
+            |k| std::mem::swap(&mut k.p, &mut k.q),
+        ];
+
+        for corrupt in corruptions {
+            let mut k = test_keypair();
+            corrupt(&mut k);
+            assert_eq!(k.validate(), Err(CryptoError::InvalidKey));
+        }
+    }
+
+    #[test]
+    fn rejects_equal_primes() {
+        let mut k = test_keypair();
+        k.q = k.p.clone();
+        k.n = &k.p * &k.q;
+        assert_eq!(k.validate(), Err(CryptoError::InvalidKey));
+    }
+
+    #[test]
+    fn rejects_composite_factors() {
+        // p = 1009 * 1013 is not prime, but n has no factor below 1000.
+        let p = string_to_number("1022117");
+        let q = string_to_number("1019");
+        let mut k = test_keypair();
+        k.n = &p * &q;
+        k.p = p;
+        k.q = q;
+        k.e = string_to_number("3");
+        assert_eq!(k.public_key().validate(), Ok(()));
+        assert_eq!(k.validate(), Err(CryptoError::InvalidKey));
+    }
+}
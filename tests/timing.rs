@@ -0,0 +1,167 @@
+//! Statistical timing tests for the private key operations, after dudect (Reparaz, Balasch and Verbauwhede,
+//! "Dude, is my code constant time?"). Each test times an operation on inputs of two classes, interleaved in a
+//! random order, and compares the two timing distributions with Welch's t-test. A |t| above 4.5 means the timing
+//! tells the classes apart.
+//!
+//! The tests are ignored by default because they are slow and need an otherwise idle machine. Run them with
+//! `cargo test --release --test timing -- --ignored --test-threads 1`.
+
+extern crate crypto_module;
+extern crate num;
+
+use crypto_module::constant_time;
+use crypto_module::rsa::{Keypair, KeypairBuilder};
+use num::bigint::BigInt;
+use num::One;
+use std::time::Instant;
+
+/// |t| above which two classes count as distinguishable.
+const THRESHOLD: f64 = 4.5;
+
+/// measurements per class.
+const SAMPLES: usize = 4_000;
+
+/// a small xorshift generator, to pick classes and inputs reproducibly.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// Welch's t statistic for the difference of the means of a and b.
+fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let mean = |xs: &[f64]| xs.iter().sum::<f64>() / xs.len() as f64;
+    let variance = |xs: &[f64], m: f64| {
+        xs.iter().map(|x| (x - m) * (x - m)).sum::<f64>() / (xs.len() - 1) as f64
+    };
+
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let standard_error =
+        (variance(a, mean_a) / a.len() as f64 + variance(b, mean_b) / b.len() as f64).sqrt();
+    (mean_a - mean_b) / standard_error
+}
+
+/// times operation on SAMPLES inputs of each class (false and true), in a random order, and returns the t
+/// statistic. The slowest tenth of all measurements is dropped, as those are mostly interrupts.
+fn t_statistic<F: FnMut(bool)>(mut operation: F) -> f64 {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut timings: Vec<(bool, f64)> = Vec::with_capacity(2 * SAMPLES);
+
+    // Warm up caches and the branch predictor.
+    for i in 0..100 {
+        operation(i % 2 == 0);
+    }
+
+    let mut counts = [0; 2];
+    while counts[0] < SAMPLES || counts[1] < SAMPLES {
+        let class = rng.next() & 1 == 1;
+        if counts[class as usize] == SAMPLES {
+            continue;
+        }
+        counts[class as usize] += 1;
+
+        let start = Instant::now();
+        operation(class);
+        timings.push((class, start.elapsed().as_nanos() as f64));
+    }
+
+    let mut sorted: Vec<f64> = timings.iter().map(|&(_, t)| t).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let cutoff = sorted[sorted.len() * 9 / 10];
+
+    let class = |wanted: bool| -> Vec<f64> {
+        timings
+            .iter()
+            .filter(|&&(class, t)| class == wanted && t <= cutoff)
+            .map(|&(_, t)| t)
+            .collect()
+    };
+    welch_t(&class(false), &class(true))
+}
+
+/// a 1024-bit keypair.
+fn keypair() -> Keypair {
+    KeypairBuilder::new()
+        .modulus_bits(1024)
+        .build(&[5; 32], &[6; 32])
+        .unwrap()
+}
+
+/// an exponent of the given number of bits with only the top and bottom bits set, or with all bits set.
+fn exponent(bits: usize, dense: bool) -> BigInt {
+    if dense {
+        (BigInt::one() << bits) - 1
+    } else {
+        (BigInt::one() << (bits - 1)) + 1
+    }
+}
+
+#[test]
+#[ignore]
+fn harness_detects_variable_time_modpow() {
+    // num-bigint's modpow skips work for zero bits, so sparse and dense exponents must be told apart.
+    let n = keypair().public_key().n().clone();
+    let base = BigInt::from(0x1234_5678_9abc_def0u64);
+    let (sparse, dense) = (exponent(1000, false), exponent(1000, true));
+
+    let t = t_statistic(|class| {
+        let exponent = if class { &dense } else { &sparse };
+        base.modpow(exponent, &n);
+    });
+    assert!(t.abs() > THRESHOLD, "t = {}", t);
+}
+
+#[test]
+#[ignore]
+fn ladder_does_not_depend_on_exponent() {
+    let n = keypair().public_key().n().clone();
+    let base = BigInt::from(0x1234_5678_9abc_def0u64);
+    let (sparse, dense) = (exponent(1000, false), exponent(1000, true));
+
+    let t = t_statistic(|class| {
+        let exponent = if class { &dense } else { &sparse };
+        constant_time::modpow(&base, exponent, &n).unwrap();
+    });
+    assert!(t.abs() < THRESHOLD, "t = {}", t);
+}
+
+#[test]
+#[ignore]
+fn decryption_does_not_depend_on_ciphertext() {
+    // One fixed small ciphertext against random ones. Neither is valid OAEP, so both take the same failure path.
+    let k = keypair();
+    let size = k.public_key().size();
+    let mut fixed = vec![0; size];
+    fixed[size - 1] = 2;
+
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let random: Vec<Vec<u8>> = (0..SAMPLES)
+        .map(|_| {
+            let mut c = rng.bytes(size);
+            // Stay below n.
+            c[0] &= 0x3f;
+            c
+        })
+        .collect();
+
+    let mut next = 0;
+    let t = t_statistic(|class| {
+        let ciphertext = if class {
+            next = (next + 1) % random.len();
+            &random[next]
+        } else {
+            &fixed
+        };
+        assert!(k.decrypt_bytes(ciphertext).is_err());
+    });
+    assert!(t.abs() < THRESHOLD, "t = {}", t);
+}